@@ -2,14 +2,44 @@
 #![allow(deprecated)]
 use anchor_lang::{
     prelude::*,
+    solana_program::{
+        hash::hashv,
+        log::sol_log_compute_units,
+        sysvar::instructions::{self as instructions_sysvar, load_current_index_checked, load_instruction_at_checked},
+    },
     system_program::{transfer, Transfer},
 };
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token::{self, Mint, Token, TokenAccount},
+};
 
 declare_id!("7HE7YJRihTBcn2Abk2kqGoT2i5o6wazR1wv8ursmxv9u");
 
 // Program constants
-const MIN_DEPOSIT_AMOUNT: u64 = 1000; // (0.000001 SOL)
-const MAX_WITHDRAWAL_AMOUNT: u64 = 1_000_000_000_000;
+const MIN_DEPOSIT_AMOUNT: u64 = 1000; // (0.000001 SOL), default VaultState::min_deposit
+const MAX_WITHDRAWAL_AMOUNT: u64 = 1_000_000_000_000; // default VaultState::max_withdrawal
+const SECONDS_PER_DAY: i64 = 86_400;
+const CLOSE_DELAY: i64 = SECONDS_PER_DAY; // default VaultState::close_delay
+const DAILY_LIMIT_DELAY: i64 = SECONDS_PER_DAY; // wait before a raised or removed daily limit applies
+const WITHDRAWAL_WINDOW: i64 = 3_600; // window max_withdrawals_per_window counts over
+const REWARD_RATE_SCALE: u128 = 1_000_000_000_000; // RewardConfig::rate is per 10^12 lamports per second
+const BPS_DENOMINATOR: u16 = 10_000;
+const MAX_ACCESS_ENTRIES: usize = 10;
+const MAX_PURPOSE_LEN: usize = 64;
+const MEMO_LEN: usize = 64;
+const TAG_LEN: usize = 8;
+const LABEL_LEN: usize = 32;
+const MAX_BATCH_SOURCES: usize = 10;
+const MAX_SPLIT_RECIPIENTS: usize = 10;
+const MAX_WHITELIST_RECIPIENTS: usize = 10;
+const MAX_WITHDRAW_FEE_BPS: u16 = 1_000; // 10%
+const MAX_TAGS: usize = 4;
+const RECOVERY_COOLDOWN: i64 = 7 * SECONDS_PER_DAY;
+const MAX_LOG_PAGE: u8 = 8; // keeps a full page of entries under the 1024-byte return data limit
+const MAX_STATE_CHUNK: usize = 1_000; // keeps a chunk and its header under the 1024-byte return data limit
+const WSOL_UNWRAP_SEED: &[u8] = b"wsol_unwrap";
+const EVENT_VERSION: u8 = 1; // schema version of the lifecycle events, bumped when their fields change
 
 #[program]
 pub mod anchor_vault {
@@ -19,335 +49,8789 @@ pub mod anchor_vault {
      * @notice Initializes a new vault for the user
      * @dev Creates both vault state account and vault system account with proper PDAs
      * @param ctx Initialize context containing user, vault_state, vault, and system_program
+     * @param index Index of the vault among the user's vaults (0 for the default vault)
+     * @param external_ref Opaque off-chain reference ID stored with the vault
+     * @param label Optional display name for the vault; None leaves it unlabeled (all zeros)
      * @return Result<()> Success or error
      */
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        msg!("Initializing vault for user: {}", ctx.accounts.user.key());
-        ctx.accounts.initialize(&ctx.bumps)?;
+    pub fn initialize(ctx: Context<Initialize>, index: u64, external_ref: u64, label: Option<[u8; LABEL_LEN]>) -> Result<()> {
+        msg!("Initializing vault {} for user: {}", index, ctx.accounts.user.key());
+        ctx.accounts.initialize(index, external_ref, label.unwrap_or_default(), &ctx.bumps)?;
         
-        emit!(VaultInitialized {
-            user: ctx.accounts.user.key(),
-            vault: ctx.accounts.vault.key(),
-            vault_state: ctx.accounts.vault_state.key(),
-        });
+        if ctx.accounts.config.emit_events {
+            let clock = Clock::get()?;
+            emit!(VaultInitialized {
+                event_version: EVENT_VERSION,
+                category: VaultInitialized::CATEGORY,
+                user: ctx.accounts.user.key(),
+                payer: ctx.accounts.user.key(),
+                vault: ctx.accounts.vault.key(),
+                vault_state: ctx.accounts.vault_state.key(),
+                index,
+                external_ref,
+                label: ctx.accounts.vault_state.label,
+                created_slot: ctx.accounts.vault_state.created_slot,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+            });
+        }
         
         Ok(())
     }
 
+    /**
+     * @notice Initializes a new vault owned by a beneficiary, paid for by another wallet
+     * @dev The beneficiary's key seeds the PDAs and becomes the vault authority without signing;
+     * the payer covers the account rent and the vault's rent-exempt seed, e.g. to gift a vault
+     * @param ctx InitializeFor context
+     * @param index Index of the vault among the beneficiary's vaults (0 for the default vault)
+     * @param external_ref Opaque off-chain reference ID stored with the vault
+     * @param label Optional display name for the vault; None leaves it unlabeled (all zeros)
+     * @return Result<()> Success or error
+     */
+    pub fn initialize_for(
+        ctx: Context<InitializeFor>,
+        index: u64,
+        external_ref: u64,
+        label: Option<[u8; LABEL_LEN]>,
+    ) -> Result<()> {
+        msg!(
+            "Initializing vault {} for beneficiary: {} paid by: {}",
+            index,
+            ctx.accounts.beneficiary.key(),
+            ctx.accounts.payer.key()
+        );
+        ctx.accounts.initialize(index, external_ref, label.unwrap_or_default(), &ctx.bumps)?;
+
+        if ctx.accounts.config.emit_events {
+            let clock = Clock::get()?;
+            emit!(VaultInitialized {
+                event_version: EVENT_VERSION,
+                category: VaultInitialized::CATEGORY,
+                user: ctx.accounts.beneficiary.key(),
+                payer: ctx.accounts.payer.key(),
+                vault: ctx.accounts.vault.key(),
+                vault_state: ctx.accounts.vault_state.key(),
+                index,
+                external_ref,
+                label: ctx.accounts.vault_state.label,
+                created_slot: ctx.accounts.vault_state.created_slot,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+            });
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Creates the program-wide config holding the emergency pause switch
+     * @dev Only callable once, by the program's upgrade authority
+     * @param ctx InitConfig context
+     * @param admin Key allowed to pause and unpause the program
+     * @return Result<()> Success or error
+     */
+    pub fn init_config(ctx: Context<InitConfig>, admin: Pubkey) -> Result<()> {
+        msg!("Initializing program config with admin: {}", admin);
+        ctx.accounts.config.set_inner(Config {
+            admin,
+            paused: false,
+            bump: ctx.bumps.config,
+            treasury: admin,
+            withdraw_fee_bps: 0,
+            cpi_guard: false,
+            emit_events: true,
+            log_compute: false,
+            min_lock_duration: 0,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Freezes or unfreezes every deposit, withdrawal and close across all vaults
+     * @dev Only callable by the config admin; refunds and configuration stay available
+     * @param ctx SetPause context
+     * @param paused Whether the program should be paused
+     * @return Result<()> Success or error
+     */
+    pub fn set_pause(ctx: Context<SetPause>, paused: bool) -> Result<()> {
+        msg!("Setting program paused to {}", paused);
+        ctx.accounts.config.paused = paused;
+
+        emit!(ProgramPauseSet {
+            admin: ctx.accounts.admin.key(),
+            paused,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the fee charged on owner withdrawals and the treasury receiving it
+     * @dev Only callable by the config admin; a zero fee disables fees
+     * @param ctx UpdateConfig context
+     * @param treasury System account receiving withdrawal fees
+     * @param withdraw_fee_bps Fee in basis points of each withdrawal, at most MAX_WITHDRAW_FEE_BPS
+     * @return Result<()> Success or error
+     */
+    pub fn set_withdraw_fee(ctx: Context<UpdateConfig>, treasury: Pubkey, withdraw_fee_bps: u16) -> Result<()> {
+        require!(withdraw_fee_bps <= MAX_WITHDRAW_FEE_BPS, VaultError::InvalidWithdrawFee);
+
+        msg!("Setting withdrawal fee to {} bps paid to treasury: {}", withdraw_fee_bps, treasury);
+        let config = &mut ctx.accounts.config;
+        config.treasury = treasury;
+        config.withdraw_fee_bps = withdraw_fee_bps;
+
+        Ok(())
+    }
+
     /**
      * @notice Deposits funds into the user's vault
      * @dev Transfers lamports from user to vault with validation
      * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
      * @param amount Amount to deposit in lamports
      * @return Result<()> Success or error
      */
-    pub fn deposit(ctx: Context<Payment>, amount: u64) -> Result<()> {
-        require!(amount >= MIN_DEPOSIT_AMOUNT, VaultError::InsufficientDepositAmount);
+    pub fn deposit(ctx: Context<Payment>, index: u64, amount: u64) -> Result<()> {
+        ctx.accounts.config.log_compute_units("deposit start");
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        ctx.accounts.vault_state.require_direct_deposit(amount)?;
         
-        msg!("Depositing {} lamports to vault: {}", amount, ctx.accounts.vault.key());
+        msg!("Depositing {} lamports to vault {}: {}", amount, index, ctx.accounts.vault.key());
         ctx.accounts.deposit(amount)?;
+        ctx.accounts.report_threshold_crossing();
         
-        emit!(FundsDeposited {
-            user: ctx.accounts.user.key(),
-            vault: ctx.accounts.vault.key(),
-            amount,
-        });
+        let points_earned = ctx.accounts.accrue_points(amount)?;
+        ctx.accounts.emit_deposited(amount, points_earned, 1, [0; MEMO_LEN])?;
+        ctx.accounts.assert_invariants()?;
+        ctx.accounts.config.log_compute_units("deposit end");
         
         Ok(())
     }
 
     /**
-     * @notice Withdraws funds from the user's vault
-     * @dev Transfers lamports from vault to user with rent exemption check
+     * @notice Deposits funds and locks withdrawals until unlock_time in one step
+     * @dev Same checks as deposit and set_lock, so the deposited funds are never unlocked in between;
+     * an active lock can be extended but not shortened
      * @param ctx Payment context
-     * @param amount Amount to withdraw in lamports
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to deposit in lamports
+     * @param unlock_time Unix timestamp from which funds can leave the vault again
      * @return Result<()> Success or error
      */
-    pub fn withdraw(ctx: Context<Payment>, amount: u64) -> Result<()> {
-        require!(amount > 0, VaultError::InvalidWithdrawAmount);
-        require!(amount <= MAX_WITHDRAWAL_AMOUNT, VaultError::ExceedsMaxWithdrawal);
-        
-        let vault_balance = ctx.accounts.vault.get_lamports();
-        let rent_exempt = Rent::get()?.minimum_balance(ctx.accounts.vault.to_account_info().data_len());
-        
-        require!(
-            vault_balance.saturating_sub(amount) >= rent_exempt,
-            VaultError::InsufficientFundsAfterWithdrawal
+    pub fn deposit_and_lock(ctx: Context<Payment>, index: u64, amount: u64, unlock_time: i64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        ctx.accounts.vault_state.require_direct_deposit(amount)?;
+        let now = ctx.accounts.vault_state.checked_now()?;
+        let min_duration = ctx.accounts.config.min_lock_duration;
+        ctx.accounts.vault_state.extend_lock(now, unlock_time, min_duration)?;
+
+        msg!(
+            "Depositing {} lamports to vault {}: {} locked until {}",
+            amount,
+            index,
+            ctx.accounts.vault.key(),
+            unlock_time
         );
-        
-        msg!("Withdrawing {} lamports from vault: {}", amount, ctx.accounts.vault.key());
-        ctx.accounts.withdraw(amount)?;
-        
-        emit!(FundsWithdrawn {
+        ctx.accounts.deposit(amount)?;
+        ctx.accounts.report_threshold_crossing();
+
+        let points_earned = ctx.accounts.accrue_points(amount)?;
+        ctx.accounts.emit_deposited(amount, points_earned, 1, [0; MEMO_LEN])?;
+
+        emit!(VaultLocked {
             user: ctx.accounts.user.key(),
-            vault: ctx.accounts.vault.key(),
-            amount,
+            vault_state: ctx.accounts.vault_state.key(),
+            unlock_time,
         });
-        
+        ctx.accounts.assert_invariants()?;
+
         Ok(())
     }
 
     /**
-     * @notice Closes the vault and transfers all remaining funds to user
-     * @dev Drains vault completely and closes the vault state account
-     * @param ctx Close context
+     * @notice Deposits funds into the user's vault with a payment reference
+     * @dev Same checks as deposit; the memo is carried by the FundsDeposited event so deposits
+     * can be matched to invoices from the logs. An all-zero memo is accepted
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to deposit in lamports
+     * @param memo Reference bytes, zero-padded
      * @return Result<()> Success or error
      */
-    pub fn close(ctx: Context<Close>) -> Result<()> {
-        let vault_balance = ctx.accounts.vault.get_lamports();
-        
-        msg!("Closing vault: {} with balance: {}", ctx.accounts.vault.key(), vault_balance);
-        ctx.accounts.close()?;
-        
-        emit!(VaultClosed {
-            user: ctx.accounts.user.key(),
-            vault: ctx.accounts.vault.key(),
-            final_balance: vault_balance,
-        });
-        
+    pub fn deposit_with_memo(ctx: Context<Payment>, index: u64, amount: u64, memo: [u8; MEMO_LEN]) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        ctx.accounts.vault_state.require_direct_deposit(amount)?;
+
+        msg!("Depositing {} lamports with memo to vault {}: {}", amount, index, ctx.accounts.vault.key());
+        ctx.accounts.deposit(amount)?;
+        ctx.accounts.report_threshold_crossing();
+
+        let points_earned = ctx.accounts.accrue_points(amount)?;
+        ctx.accounts.emit_deposited(amount, points_earned, 1, memo)?;
+        ctx.accounts.assert_invariants()?;
+
         Ok(())
     }
-}
 
-/**
- * @notice Account validation struct for vault initialization
- * @dev Creates PDA accounts for vault state and vault with proper seeds
- */
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+    /**
+     * @notice Deposits funds into the user's vault from several wallets the user controls
+     * @dev Funding wallets are passed as signing, writable remaining accounts, one per amount;
+     * the deposit checks and a single FundsDeposited event cover the total
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param amounts Amount in lamports paid by each funding wallet, in remaining account order
+     * @return Result<()> Success or error
+     */
+    pub fn batch_deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, Payment<'info>>,
+        index: u64,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        require!(amounts.len() <= MAX_BATCH_SOURCES, VaultError::TooManySources);
 
-    #[account(
-        init,
-        payer = user,
-        space = VaultState::DISCRIMINATOR.len() + VaultState::INIT_SPACE,
-        seeds = [VaultState::STATE_SEED, user.key().as_ref()],
-        bump,
-    )]
-    pub vault_state: Account<'info, VaultState>,
+        let sources = ctx.remaining_accounts;
+        require!(
+            !amounts.is_empty() && sources.len() == amounts.len(),
+            VaultError::InvalidFundingSources
+        );
+        require!(
+            sources.iter().all(|source| source.is_signer && source.is_writable),
+            VaultError::InvalidFundingSources
+        );
 
-    #[account(
-        mut,
-        seeds = [VaultState::VAULT_SEED, user.key().as_ref()],
-        bump,
-    )]
-    pub vault: SystemAccount<'info>,
+        let amount = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(VaultError::MathOverflow)?;
+        ctx.accounts.vault_state.require_direct_deposit(amount)?;
 
-    pub system_program: Program<'info, System>,
-}
+        msg!("Depositing {} lamports from {} sources to vault {}: {}", amount, sources.len(), index, ctx.accounts.vault.key());
+        ctx.accounts.deposit_from(sources, &amounts)?;
+        ctx.accounts.report_threshold_crossing();
+
+        let points_earned = ctx.accounts.accrue_points(amount)?;
+        ctx.accounts.emit_deposited(amount, points_earned, sources.len() as u8, [0; MEMO_LEN])?;
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
+    }
 
-impl<'info> Initialize<'info> {
     /**
-     * @notice Initializes vault state and funds vault with rent-exempt amount
-     * @dev Sets bump seeds and transfers minimum balance for rent exemption
-     * @param bumps Bump seeds from account initialization
+     * @notice Deposits funds into the user's vault and attributes them to a marketing campaign
+     * @dev The deposit goes through the same checks and bookkeeping as deposit; the campaign's
+     * stats account is created on its first deposit
+     * @param ctx CampaignPayment context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to deposit in lamports
+     * @param campaign_id Campaign the deposit is attributed to
      * @return Result<()> Success or error
      */
-    fn initialize(&mut self, bumps: &InitializeBumps) -> Result<()> {
-        // Initialize vault state with bump seeds
-        self.vault_state.set_inner(VaultState {
-            state_bump: bumps.vault_state,
-            vault_bump: bumps.vault,
-        });
+    pub fn deposit_with_campaign(ctx: Context<CampaignPayment>, index: u64, amount: u64, campaign_id: u32) -> Result<()> {
+        let payment = &mut ctx.accounts.payment;
+        payment.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        payment.vault_state.require_direct_deposit(amount)?;
 
-        // Calculate and transfer rent-exempt amount to vault
-        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        msg!("Depositing {} lamports to vault {}: {} for campaign: {}", amount, index, payment.vault.key(), campaign_id);
+        payment.deposit(amount)?;
+        payment.report_threshold_crossing();
 
-        let transfer_accounts = Transfer {
-            from: self.user.to_account_info(),
-            to: self.vault.to_account_info(),
-        };
+        let points_earned = payment.accrue_points(amount)?;
+        payment.emit_deposited(amount, points_earned, 1, [0; MEMO_LEN])?;
 
-        let transfer_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+        let stats = ctx.accounts.campaign_stats.record(campaign_id, amount, ctx.bumps.campaign_stats)?;
+
+        emit!(CampaignDeposit {
+            campaign_id,
+            user: ctx.accounts.payment.user.key(),
+            amount,
+            total_deposited: stats.total_deposited,
+            deposit_count: stats.deposit_count,
+        });
+        ctx.accounts.payment.assert_invariants()?;
 
-        transfer(transfer_ctx, rent_exempt)
+        Ok(())
     }
-}
 
-/**
- * @notice Account validation struct for deposit and withdrawal operations
- * @dev Validates vault ownership and account relationships
- */
-#[derive(Accounts)]
-pub struct Payment<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+    /**
+     * @notice Deposits the round-up of a purchase to the next multiple of round_to
+     * @dev Saves round_to - (purchase_amount % round_to), so an exact multiple saves a full unit
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param purchase_amount Purchase amount in lamports being rounded up
+     * @param round_to Rounding unit in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn round_up_deposit(ctx: Context<Payment>, index: u64, purchase_amount: u64, round_to: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        require!(round_to > 0, VaultError::InvalidRoundingUnit);
 
-    #[account(
-        seeds = [VaultState::STATE_SEED, user.key().as_ref()],
-        bump = vault_state.state_bump
-    )]
-    pub vault_state: Account<'info, VaultState>,
+        let amount = round_to
+            .checked_sub(purchase_amount % round_to)
+            .ok_or(VaultError::MathOverflow)?;
+        ctx.accounts.vault_state.require_direct_deposit(amount)?;
 
-    #[account(
-        mut,
-        seeds = [VaultState::VAULT_SEED, user.key().as_ref()],
-        bump = vault_state.vault_bump
-    )]
-    pub vault: SystemAccount<'info>,
+        msg!("Rounding up purchase of {} to {}: depositing {} lamports to vault {}", purchase_amount, round_to, amount, index);
+        ctx.accounts.deposit(amount)?;
+        ctx.accounts.report_threshold_crossing();
 
-    pub system_program: Program<'info, System>,
-}
+        let points_earned = ctx.accounts.accrue_points(amount)?;
+        ctx.accounts.emit_deposited(amount, points_earned, 1, [0; MEMO_LEN])?;
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
+    }
 
-impl<'info> Payment<'info> {
     /**
-     * @notice Deposits funds from user to vault
-     * @dev Transfers lamports using system program CPI
-     * @param amount Amount to deposit in lamports
+     * @notice Pays a recipient from the user's wallet and saves a share of the payment
+     * @dev Both transfers come from the user's wallet in the same instruction; the saved
+     * share goes through the same checks and bookkeeping as deposit
+     * @param ctx PayAndSave context
+     * @param index Index of the vault among the user's vaults
+     * @param payment_amount Amount paid to the recipient in lamports
+     * @param save_bps Share of the payment deposited into the vault, out of BPS_DENOMINATOR
+     * @param recipient Account being paid
      * @return Result<()> Success or error
      */
-    fn deposit(&mut self, amount: u64) -> Result<()> {
+    pub fn pay_and_save(ctx: Context<PayAndSave>, index: u64, payment_amount: u64, save_bps: u16, recipient: Pubkey) -> Result<()> {
+        require!(save_bps <= BPS_DENOMINATOR, VaultError::InvalidPercent);
+        require!(payment_amount > 0, VaultError::InvalidPayment);
+
+        let payment = &mut ctx.accounts.payment;
+        require_keys_neq!(recipient, payment.user.key(), VaultError::InvalidPayment);
+        require_keys_neq!(recipient, payment.vault.key(), VaultError::InvalidPayment);
+
+        let amount = (u128::from(payment_amount) * u128::from(save_bps) / u128::from(BPS_DENOMINATOR)) as u64;
+        payment.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        payment.vault_state.require_direct_deposit(amount)?;
+
+        msg!("Paying {} lamports to: {}", payment_amount, recipient);
         let transfer_accounts = Transfer {
-            from: self.user.to_account_info(),
-            to: self.vault.to_account_info(),
+            from: payment.user.to_account_info(),
+            to: ctx.accounts.payee.to_account_info(),
         };
+        let transfer_ctx = CpiContext::new(payment.system_program.to_account_info(), transfer_accounts);
+        transfer(transfer_ctx, payment_amount)?;
 
-        let transfer_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+        msg!("Saving {} lamports to vault {}: {}", amount, index, payment.vault.key());
+        payment.deposit(amount)?;
+        payment.report_threshold_crossing();
 
-        transfer(transfer_ctx, amount)
+        let points_earned = payment.accrue_points(amount)?;
+        payment.emit_deposited(amount, points_earned, 1, [0; MEMO_LEN])?;
+        payment.assert_invariants()?;
+
+        Ok(())
     }
 
     /**
-     * @notice Withdraws funds from vault to user
-     * @dev Uses PDA signing to authorize transfer from vault
-     * @param amount Amount to withdraw in lamports
+     * @notice Configures two-step confirmation for large deposits
+     * @dev Only callable by the vault owner; deposits above the threshold must go through
+     * propose_deposit and confirm_deposit, and a zero threshold disables confirmation
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param confirm_threshold Deposits above this amount in lamports need confirmation
+     * @param confirm_window Seconds after a proposal within which it can be confirmed
      * @return Result<()> Success or error
      */
-    fn withdraw(&mut self, amount: u64) -> Result<()> {
-        let transfer_accounts = Transfer {
-            from: self.vault.to_account_info(),
-            to: self.user.to_account_info(),
-        };
-
-        // Create PDA seeds for vault signing
-        let seeds = &[
-            VaultState::VAULT_SEED,
-            self.user.to_account_info().key.as_ref(),
-            &[self.vault_state.vault_bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
-
-        let transfer_ctx = CpiContext::new_with_signer(
-            self.system_program.to_account_info(),
-            transfer_accounts,
-            signer_seeds,
+    pub fn set_deposit_confirmation(ctx: Context<UpdateVault>, index: u64, confirm_threshold: u64, confirm_window: i64) -> Result<()> {
+        require!(
+            confirm_threshold == 0 || confirm_window > 0,
+            VaultError::InvalidConfirmationWindow
         );
 
-        transfer(transfer_ctx, amount)?;
-
-        // Verify vault maintains rent exemption after withdrawal
-        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
-        require_gte!(self.vault.get_lamports(), rent_exempt);
+        msg!(
+            "Requiring confirmation for deposits above {} lamports within {}s for vault state {}: {}",
+            confirm_threshold,
+            confirm_window,
+            index,
+            ctx.accounts.vault_state.key()
+        );
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.confirm_threshold = confirm_threshold;
+        vault_state.confirm_window = confirm_window;
 
         Ok(())
     }
-}
 
-/**
- * @notice Account validation struct for vault closure
- * @dev Closes vault state account and transfers remaining funds
- */
-#[derive(Accounts)]
-pub struct Close<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+    /**
+     * @notice Records the intent to make a deposit that is transferred on confirmation
+     * @dev Replaces any earlier proposal; nothing is transferred until confirm_deposit
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to deposit in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn propose_deposit(ctx: Context<Payment>, index: u64, amount: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        ctx.accounts.vault_state.require_min_deposit(amount)?;
 
-    #[account(
-        mut,
-        close = user,
-        seeds = [VaultState::STATE_SEED, user.key().as_ref()],
-        bump = vault_state.state_bump
-    )]
-    pub vault_state: Account<'info, VaultState>,
+        let now = ctx.accounts.vault_state.checked_now()?;
 
-    #[account(
-        mut,
-        seeds = [VaultState::VAULT_SEED, user.key().as_ref()],
-        bump = vault_state.vault_bump
-    )]
-    pub vault: SystemAccount<'info>,
+        msg!("Proposing deposit of {} lamports to vault {}: {}", amount, index, ctx.accounts.vault.key());
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.proposed_deposit = amount;
+        vault_state.proposed_at = now;
 
-    pub system_program: Program<'info, System>,
-}
+        Ok(())
+    }
 
-impl<'info> Close<'info> {
     /**
-     * @notice Closes vault and transfers all remaining funds to user
-     * @dev Drains vault completely using PDA signing
+     * @notice Transfers a proposed deposit into the vault
+     * @dev Must be called within confirm_window seconds of the proposal
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
      * @return Result<()> Success or error
      */
-    fn close(&mut self) -> Result<()> {
-        let transfer_accounts = Transfer {
-            from: self.vault.to_account_info(),
-            to: self.user.to_account_info(),
-        };
+    pub fn confirm_deposit(ctx: Context<Payment>, index: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
 
-        // Create PDA seeds for vault signing
-        let seeds = &[
-            VaultState::VAULT_SEED,
-            self.user.to_account_info().key.as_ref(),
-            &[self.vault_state.vault_bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
+        let amount = ctx.accounts.vault_state.proposed_deposit;
+        require!(amount > 0, VaultError::NoProposedDeposit);
 
-        let transfer_ctx = CpiContext::new_with_signer(
-            self.system_program.to_account_info(),
-            transfer_accounts,
-            signer_seeds,
+        let now = ctx.accounts.vault_state.checked_now()?;
+        let vault_state = &ctx.accounts.vault_state;
+        require!(
+            now <= vault_state.proposed_at.saturating_add(vault_state.confirm_window),
+            VaultError::ConfirmationExpired
         );
 
-        transfer(transfer_ctx, self.vault.get_lamports())
+        msg!("Confirming deposit of {} lamports to vault {}: {}", amount, index, ctx.accounts.vault.key());
+        ctx.accounts.deposit(amount)?;
+        ctx.accounts.report_threshold_crossing();
+
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.proposed_deposit = 0;
+        vault_state.proposed_at = 0;
+
+        let points_earned = ctx.accounts.accrue_points(amount)?;
+        ctx.accounts.emit_deposited(amount, points_earned, 1, [0; MEMO_LEN])?;
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
     }
-}
 
-/**
- * @notice Vault state account data structure
- * @dev Stores bump seeds for PDA derivation
- */
-#[account]
-#[derive(InitSpace)]
-pub struct VaultState {
-    /// Bump seed for vault state PDA
-    pub state_bump: u8,
-    /// Bump seed for vault PDA
-    pub vault_bump: u8,
-}
+    /**
+     * @notice Withdraws funds from the user's vault
+     * @dev Transfers lamports from vault to user with rent exemption check; with auto_close on,
+     * a withdrawal that leaves only rent behind also closes the vault
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to withdraw in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn withdraw(ctx: Context<Payment>, index: u64, amount: u64) -> Result<()> {
+        ctx.accounts.config.log_compute_units("withdraw start");
+        ctx.accounts.check_withdrawal(amount, ctx.remaining_accounts)?;
 
-impl VaultState {
-    /// Seed constant for vault state PDA
-    pub const STATE_SEED: &'static [u8] = b"state";
-    /// Seed constant for vault PDA
-    pub const VAULT_SEED: &'static [u8] = b"vault";
-}
+        msg!("Withdrawing {} lamports from vault {}: {}", amount, index, ctx.accounts.vault.key());
+        let user = ctx.accounts.user.to_account_info();
+        let fee = ctx.accounts.withdraw(amount, user)?;
 
-// Events for program activity tracking
+        ctx.accounts.emit_withdrawn(amount, fee, ctx.accounts.user.key())?;
+        ctx.accounts.auto_close(index)?;
+        ctx.accounts.assert_invariants()?;
+        ctx.accounts.config.log_compute_units("withdraw end");
 
-/**
- * @notice Event emitted when a vault is initialized
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws funds from the user's vault straight to a third party
+     * @dev Applies every withdraw check; only the destination of the transfer differs
+     * @param ctx WithdrawTo context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to withdraw in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn withdraw_to(ctx: Context<WithdrawTo>, index: u64, amount: u64) -> Result<()> {
+        let payment = &mut ctx.accounts.payment;
+        payment.check_withdrawal(amount, ctx.remaining_accounts)?;
+
+        let recipient = ctx.accounts.recipient.key();
+        msg!("Withdrawing {} lamports from vault {}: {} to: {}", amount, index, payment.vault.key(), recipient);
+        let fee = payment.withdraw(amount, ctx.accounts.recipient.to_account_info())?;
+
+        payment.emit_withdrawn(amount, fee, recipient)?;
+        payment.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Pays several recipients from the user's vault in one transaction
+     * @dev Recipients are passed as writable remaining accounts, one per amount, followed by
+     * any approver signatures. The withdraw checks apply to the total; each payment is then
+     * charged the withdrawal fee, counted as a withdrawal and reported by its own FundsWithdrawn
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param amounts Amount in lamports paid to each recipient, in remaining account order
+     * @return Result<()> Success or error
+     */
+    pub fn withdraw_split<'info>(
+        ctx: Context<'_, '_, '_, 'info, Payment<'info>>,
+        index: u64,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        require!(amounts.len() <= MAX_SPLIT_RECIPIENTS, VaultError::TooManyRecipients);
+        require!(
+            ctx.remaining_accounts.len() >= amounts.len(),
+            VaultError::RecipientCountMismatch
+        );
+        let (recipients, approvals) = ctx.remaining_accounts.split_at(amounts.len());
+        require!(
+            recipients.iter().all(|recipient| recipient.is_writable && recipient.owner == &System::id()),
+            VaultError::InvalidRecipient
+        );
+        require!(amounts.iter().all(|amount| *amount > 0), VaultError::InvalidWithdrawAmount);
+
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(VaultError::MathOverflow)?;
+        ctx.accounts.check_withdrawal(total, approvals)?;
+
+        msg!(
+            "Withdrawing {} lamports to {} recipients from vault {}: {}",
+            total,
+            recipients.len(),
+            index,
+            ctx.accounts.vault.key()
+        );
+        for (recipient, amount) in recipients.iter().zip(amounts) {
+            let fee = ctx.accounts.withdraw(amount, recipient.clone())?;
+            ctx.accounts.emit_withdrawn(amount, fee, recipient.key())?;
+        }
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Approves a withdrawal recipient for withdraw_to_whitelisted
+     * @dev Only callable by the owner, who pays for the whitelist on first use; the whitelist
+     * is shared by all of the owner's vaults and holds at most MAX_WHITELIST_RECIPIENTS entries
+     * @param ctx AddRecipient context
+     * @param recipient Address to approve
+     * @return Result<()> Success or error
+     */
+    pub fn add_recipient(ctx: Context<AddRecipient>, recipient: Pubkey) -> Result<()> {
+        require!(recipient != Pubkey::default(), VaultError::InvalidRecipient);
+
+        let whitelist = &mut ctx.accounts.whitelist;
+        require!(!whitelist.recipients.contains(&recipient), VaultError::DuplicateRecipient);
+        require!(whitelist.recipients.len() < MAX_WHITELIST_RECIPIENTS, VaultError::WhitelistFull);
+
+        msg!("Whitelisting recipient {} for owner: {}", recipient, ctx.accounts.user.key());
+        whitelist.owner = ctx.accounts.user.key();
+        whitelist.bump = ctx.bumps.whitelist;
+        whitelist.recipients.push(recipient);
+
+        emit!(WhitelistUpdated {
+            owner: ctx.accounts.user.key(),
+            recipient,
+            added: true,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Removes an approved withdrawal recipient
+     * @dev Only callable by the owner; the freed entry is reused by the next add_recipient
+     * @param ctx RemoveRecipient context
+     * @param recipient Address to remove
+     * @return Result<()> Success or error
+     */
+    pub fn remove_recipient(ctx: Context<RemoveRecipient>, recipient: Pubkey) -> Result<()> {
+        let whitelist = &mut ctx.accounts.whitelist;
+        let position = whitelist
+            .recipients
+            .iter()
+            .position(|entry| *entry == recipient)
+            .ok_or(VaultError::RecipientNotWhitelisted)?;
+
+        msg!("Removing whitelisted recipient {} for owner: {}", recipient, ctx.accounts.user.key());
+        whitelist.recipients.swap_remove(position);
+
+        emit!(WhitelistUpdated {
+            owner: ctx.accounts.user.key(),
+            recipient,
+            added: false,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws funds from the user's vault to a whitelisted recipient
+     * @dev Applies every withdraw check, like withdraw_to, and additionally requires the
+     * recipient to be on the owner's whitelist
+     * @param ctx WithdrawToWhitelisted context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to withdraw in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn withdraw_to_whitelisted(ctx: Context<WithdrawToWhitelisted>, index: u64, amount: u64) -> Result<()> {
+        let recipient = ctx.accounts.recipient.key();
+        require!(
+            ctx.accounts.whitelist.recipients.contains(&recipient),
+            VaultError::RecipientNotWhitelisted
+        );
+
+        let payment = &mut ctx.accounts.payment;
+        payment.check_withdrawal(amount, ctx.remaining_accounts)?;
+
+        msg!("Withdrawing {} lamports from vault {}: {} to whitelisted: {}", amount, index, payment.vault.key(), recipient);
+        let fee = payment.withdraw(amount, ctx.accounts.recipient.to_account_info())?;
+
+        payment.emit_withdrawn(amount, fee, recipient)?;
+        payment.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws a share of the vault's withdrawable balance
+     * @dev The amount is computed on-chain from the balance above rent and committed funds,
+     * so it can't go stale between a client read and the transaction; every withdraw check applies
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param basis_points Share of the withdrawable balance to withdraw, out of BPS_DENOMINATOR
+     * @return Result<()> Success or error
+     */
+    pub fn withdraw_percent(ctx: Context<Payment>, index: u64, basis_points: u16) -> Result<()> {
+        require!(basis_points <= BPS_DENOMINATOR, VaultError::InvalidPercent);
+
+        let amount = ctx.accounts.withdrawable_share(basis_points)?;
+        ctx.accounts.check_withdrawal(amount, ctx.remaining_accounts)?;
+
+        msg!("Withdrawing {} bps ({} lamports) from vault {}: {}", basis_points, amount, index, ctx.accounts.vault.key());
+        let user = ctx.accounts.user.to_account_info();
+        let fee = ctx.accounts.withdraw(amount, user)?;
+
+        ctx.accounts.emit_withdrawn(amount, fee, ctx.accounts.user.key())?;
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws everything above rent and committed funds but keeps the vault open
+     * @dev Subject to every withdraw check; the vault state and the rent-exempt seed stay in
+     * place so the vault can be reused without initializing it again. Emits FundsWithdrawn like
+     * any withdrawal, followed by VaultDrained
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn drain(ctx: Context<Payment>, index: u64) -> Result<()> {
+        let amount = ctx.accounts.withdrawable_share(BPS_DENOMINATOR)?;
+        require!(amount > 0, VaultError::OnlyRentRemains);
+        ctx.accounts.check_withdrawal(amount, ctx.remaining_accounts)?;
+
+        msg!("Draining {} lamports from vault {}: {}", amount, index, ctx.accounts.vault.key());
+        let user = ctx.accounts.user.to_account_info();
+        let fee = ctx.accounts.withdraw(amount, user)?;
+
+        ctx.accounts.emit_withdrawn(amount, fee, ctx.accounts.user.key())?;
+
+        emit!(VaultDrained {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            amount,
+        });
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Adds lamports to the vault that withdrawals must always leave behind
+     * @dev The extra lamports are not a deposit: they raise rent_buffer, which counts towards the
+     * balance floor, so the withdrawable balance stays the same. The buffer is only paid out by a close
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param extra Amount added to the buffer in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn fund_rent_buffer(ctx: Context<Payment>, index: u64, extra: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        require!(extra > 0, VaultError::InsufficientDepositAmount);
+
+        msg!("Funding rent buffer with {} lamports for vault {}: {}", extra, index, ctx.accounts.vault.key());
+        ctx.accounts.fund_rent_buffer(extra)?;
+
+        emit!(RentBufferFunded {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            extra,
+            rent_buffer: ctx.accounts.vault_state.rent_buffer,
+        });
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Releases the current withdrawable balance linearly over a vesting window
+     * @dev Snapshots the withdrawable balance as vesting_total; until the window ends, withdrawals
+     * may only take the vested share minus what was withdrawn since it started. A running window
+     * cannot be restarted
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param duration Length of the vesting window in seconds
+     * @return Result<()> Success or error
+     */
+    pub fn start_vesting(ctx: Context<Payment>, index: u64, duration: i64) -> Result<()> {
+        require!(duration > 0, VaultError::InvalidVestingDuration);
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(!ctx.accounts.vault_state.is_vesting(now), VaultError::VestingActive);
+
+        let total = ctx.accounts.withdrawable_share(BPS_DENOMINATOR)?;
+        let end = now.checked_add(duration).ok_or(VaultError::MathOverflow)?;
+
+        msg!("Vesting {} lamports of vault {}: {} from {} until {}", total, index, ctx.accounts.vault.key(), now, end);
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.vesting_start = now;
+        vault_state.vesting_end = end;
+        vault_state.vesting_total = total;
+        vault_state.vesting_withdrawn = 0;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws funds but diverts a share into a locked savings tranche in the vault
+     * @dev Every withdraw check applies to the full amount; the diverted share stays in the vault
+     * and cannot leave it until savings_lock_period seconds after the latest diversion
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to withdraw in lamports, before diverting the saved share
+     * @param save_bps Share of amount kept in the vault, out of BPS_DENOMINATOR
+     * @return Result<()> Success or error
+     */
+    pub fn withdraw_with_save(ctx: Context<Payment>, index: u64, amount: u64, save_bps: u16) -> Result<()> {
+        require!(save_bps <= BPS_DENOMINATOR, VaultError::InvalidPercent);
+        require!(ctx.accounts.vault_state.savings_lock_period > 0, VaultError::SavingsLockNotSet);
+        ctx.accounts.check_withdrawal(amount, ctx.remaining_accounts)?;
+
+        let saved = (u128::from(amount) * u128::from(save_bps) / u128::from(BPS_DENOMINATOR)) as u64;
+        let paid = amount.checked_sub(saved).ok_or(VaultError::MathOverflow)?;
+
+        msg!("Withdrawing {} lamports from vault {}: {} and locking {} as savings", paid, index, ctx.accounts.vault.key(), saved);
+        if paid > 0 {
+            let user = ctx.accounts.user.to_account_info();
+            let fee = ctx.accounts.withdraw(paid, user)?;
+            ctx.accounts.emit_withdrawn(paid, fee, ctx.accounts.user.key())?;
+        }
+
+        let now = ctx.accounts.vault_state.checked_now()?;
+        ctx.accounts.vault_state.lock_savings(now, saved)?;
+
+        emit!(SavingsLocked {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            amount: saved,
+            tranche: ctx.accounts.vault_state.savings_tranche,
+            unlock_time: ctx.accounts.vault_state.savings_unlock_time,
+        });
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets how long withdraw_with_save keeps its diverted savings locked
+     * @dev Only callable by the vault owner; zero disables withdraw_with_save, and a tranche
+     * that is already locked keeps its unlock time
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param lock_period Seconds the tranche stays locked after each diversion
+     * @return Result<()> Success or error
+     */
+    pub fn set_savings_lock(ctx: Context<UpdateVault>, index: u64, lock_period: i64) -> Result<()> {
+        require!(lock_period >= 0, VaultError::InvalidSavingsLock);
+
+        msg!("Setting savings lock period to {}s for vault state {}: {}", lock_period, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.savings_lock_period = lock_period;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws funds to the vault owner on the owner's behalf
+     * @dev Only callable by the vault's delegate; every withdraw check applies and the funds
+     * always go to the owner, less the withdrawal fee
+     * @param ctx WithdrawAsDelegate context
+     * @param index Index of the vault among the owner's vaults
+     * @param amount Amount to withdraw in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn withdraw_as_delegate(ctx: Context<WithdrawAsDelegate>, index: u64, amount: u64) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        ctx.accounts.vault_state.check_withdrawal(vault, amount, Some(ctx.remaining_accounts))?;
+
+        msg!("Delegate {} withdrawing {} lamports from vault {}: {}", ctx.accounts.delegate.key(), amount, index, ctx.accounts.vault.key());
+        let fee = ctx.accounts.withdraw(amount)?;
+
+        emit!(DelegateWithdrawn {
+            delegate: ctx.accounts.delegate.key(),
+            owner: ctx.accounts.owner.key(),
+            vault: ctx.accounts.vault.key(),
+            amount,
+            fee,
+        });
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the account allowed to withdraw to the owner on the owner's behalf
+     * @dev Only callable by the vault owner; the default pubkey removes the delegate
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param delegate Account allowed to call withdraw_as_delegate
+     * @return Result<()> Success or error
+     */
+    pub fn set_delegate(ctx: Context<UpdateVault>, index: u64, delegate: Pubkey) -> Result<()> {
+        require_keys_neq!(delegate, ctx.accounts.user.key(), VaultError::InvalidDelegate);
+
+        msg!("Setting delegate to {} for vault state {}: {}", delegate, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.delegate = delegate;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Queues a withdrawal that anyone can execute once execute_after has passed
+     * @dev Only callable by the vault owner; approvals for the amount are checked now, passed
+     * as remaining accounts, while every other withdraw check runs at execution. Scheduling
+     * again replaces the pending withdrawal
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to withdraw in lamports
+     * @param recipient Account receiving the funds
+     * @param execute_after Unix timestamp from which the withdrawal can be executed
+     * @return Result<()> Success or error
+     */
+    pub fn schedule_withdrawal(ctx: Context<UpdateVault>, index: u64, amount: u64, recipient: Pubkey, execute_after: i64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(
+            amount > 0 && recipient != Pubkey::default() && execute_after > now,
+            VaultError::InvalidScheduledWithdrawal
+        );
+        ctx.accounts.vault_state.require_not_renounced()?;
+        ctx.accounts.vault_state.require_approvals(amount, ctx.remaining_accounts)?;
+
+        msg!(
+            "Scheduling withdrawal of {} lamports from vault state {}: {} to: {} after {}",
+            amount,
+            index,
+            ctx.accounts.vault_state.key(),
+            recipient,
+            execute_after
+        );
+        ctx.accounts.vault_state.scheduled_withdrawal = ScheduledWithdrawal {
+            amount,
+            recipient,
+            execute_after,
+        };
+
+        emit!(WithdrawalScheduled {
+            user: ctx.accounts.user.key(),
+            vault_state: ctx.accounts.vault_state.key(),
+            amount,
+            recipient,
+            execute_after,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Drops the pending scheduled withdrawal
+     * @dev Only callable by the vault owner
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or NoScheduledWithdrawal
+     */
+    pub fn cancel_scheduled(ctx: Context<UpdateVault>, index: u64) -> Result<()> {
+        let scheduled = ctx.accounts.vault_state.scheduled_withdrawal;
+        require!(scheduled.is_pending(), VaultError::NoScheduledWithdrawal);
+
+        msg!("Cancelling scheduled withdrawal for vault state {}: {}", index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.scheduled_withdrawal = ScheduledWithdrawal::default();
+
+        emit!(ScheduledWithdrawalCancelled {
+            user: ctx.accounts.user.key(),
+            vault_state: ctx.accounts.vault_state.key(),
+            amount: scheduled.amount,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Pays out the pending scheduled withdrawal once its time has come
+     * @dev Callable by anyone, e.g. a keeper; every withdraw check except approvals applies at
+     * execution, and the funds always go to the scheduled recipient, less the withdrawal fee
+     * @param ctx ExecuteScheduled context
+     * @param index Index of the vault among the owner's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn execute_scheduled(ctx: Context<ExecuteScheduled>, index: u64) -> Result<()> {
+        let scheduled = ctx.accounts.vault_state.scheduled_withdrawal;
+        require!(scheduled.is_pending(), VaultError::NoScheduledWithdrawal);
+
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(now >= scheduled.execute_after, VaultError::NotYetExecutable);
+
+        let vault = &ctx.accounts.vault;
+        ctx.accounts.vault_state.check_withdrawal(vault, scheduled.amount, None)?;
+
+        msg!(
+            "Executing scheduled withdrawal of {} lamports from vault {}: {} to: {}",
+            scheduled.amount,
+            index,
+            ctx.accounts.vault.key(),
+            scheduled.recipient
+        );
+        let fee = ctx.accounts.execute(scheduled.amount, now)?;
+
+        if ctx.accounts.config.emit_events {
+            let clock = Clock::get()?;
+            emit!(FundsWithdrawn {
+                event_version: EVENT_VERSION,
+                category: FundsWithdrawn::CATEGORY,
+                user: ctx.accounts.owner.key(),
+                vault: ctx.accounts.vault.key(),
+                recipient: scheduled.recipient,
+                amount: scheduled.amount,
+                fee,
+                total_withdrawn: ctx.accounts.vault_state.total_withdrawn,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+                initiated_by_admin: false,
+            });
+        }
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws from a vault on behalf of its owner, for custodial operators
+     * @dev Only callable by the config admin, on vaults whose owner opted in with set_admin_managed;
+     * the funds always go to the vault's stored authority, never to the admin, less the withdrawal
+     * fee, and every owner withdrawal check applies, approvals included
+     * @param ctx AdminWithdraw context
+     * @param index Index of the vault among the owner's vaults
+     * @param amount Amount to withdraw in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn admin_withdraw(ctx: Context<AdminWithdraw>, index: u64, amount: u64) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        ctx.accounts.vault_state.check_withdrawal(vault, amount, Some(ctx.remaining_accounts))?;
+
+        msg!(
+            "Admin withdrawing {} lamports from vault {}: {} to owner: {}",
+            amount,
+            index,
+            ctx.accounts.vault.key(),
+            ctx.accounts.owner.key()
+        );
+        let now = ctx.accounts.vault_state.checked_now()?;
+        let fee = ctx.accounts.withdraw(amount, now)?;
+
+        if ctx.accounts.config.emit_events {
+            let clock = Clock::get()?;
+            emit!(FundsWithdrawn {
+                event_version: EVENT_VERSION,
+                category: FundsWithdrawn::CATEGORY,
+                user: ctx.accounts.owner.key(),
+                vault: ctx.accounts.vault.key(),
+                recipient: ctx.accounts.owner.key(),
+                amount,
+                fee,
+                total_withdrawn: ctx.accounts.vault_state.total_withdrawn,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+                initiated_by_admin: true,
+            });
+        }
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the minimum amount accepted by a single deposit
+     * @dev Only callable by the vault owner; applies to every lamport deposit into the vault
+     * and to funding contributions
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param min_deposit Minimum deposit amount in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_min_deposit(ctx: Context<UpdateVault>, index: u64, min_deposit: u64) -> Result<()> {
+        require!(min_deposit > 0, VaultError::InvalidMinDeposit);
+
+        msg!("Setting minimum deposit to {} lamports for vault state {}: {}", min_deposit, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.min_deposit = min_deposit;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the minimum amount accepted by a single withdrawal
+     * @dev Only callable by the vault owner; zero disables the check
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param min_withdrawal Minimum withdrawal amount in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_min_withdrawal(ctx: Context<UpdateVault>, index: u64, min_withdrawal: u64) -> Result<()> {
+        msg!("Setting minimum withdrawal to {} lamports for vault state {}: {}", min_withdrawal, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.min_withdrawal = min_withdrawal;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the maximum amount a single withdrawal can take
+     * @dev Only callable by the vault owner; vaults start at MAX_WITHDRAWAL_AMOUNT and the
+     * ceiling can be raised or lowered but not removed
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param max Maximum withdrawal amount in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_max_withdrawal(ctx: Context<UpdateVault>, index: u64, max: u64) -> Result<()> {
+        require!(max > 0, VaultError::InvalidMaxWithdrawal);
+
+        msg!("Setting maximum withdrawal to {} lamports for vault state {}: {}", max, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.max_withdrawal = max;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Locks withdrawals until unlock_time
+     * @dev Only callable by the vault owner; an active lock can be extended but not shortened,
+     * a new unlock time must be at least the config's min_lock_duration away, and withdraw,
+     * spend_granted, outbound mirror syncs, migrate_out and close all honour the lock
+     * @param ctx SetLock context
+     * @param index Index of the vault among the user's vaults
+     * @param unlock_time Unix timestamp from which funds can leave the vault again
+     * @return Result<()> Success or error
+     */
+    pub fn set_lock(ctx: Context<SetLock>, index: u64, unlock_time: i64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+
+        msg!("Locking vault state {}: {} until {}", index, ctx.accounts.vault_state.key(), unlock_time);
+        let min_duration = ctx.accounts.config.min_lock_duration;
+        ctx.accounts.vault_state.extend_lock(now, unlock_time, min_duration)?;
+
+        emit!(VaultLocked {
+            user: ctx.accounts.user.key(),
+            vault_state: ctx.accounts.vault_state.key(),
+            unlock_time,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Locks part of the balance until unlock_time, leaving the rest withdrawable
+     * @dev Only callable by the vault owner; an active partial lock can be raised or extended
+     * but neither reduced nor shortened, and the locked portion frees itself at unlock_time
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Lamports kept in the vault until unlock_time
+     * @param unlock_time Unix timestamp from which the locked portion can be withdrawn
+     * @return Result<()> Success or error
+     */
+    pub fn set_partial_lock(ctx: Context<UpdateVault>, index: u64, amount: u64, unlock_time: i64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+
+        msg!(
+            "Locking {} lamports of vault state {}: {} until {}",
+            amount,
+            index,
+            ctx.accounts.vault_state.key(),
+            unlock_time
+        );
+        ctx.accounts.vault_state.extend_partial_lock(now, amount, unlock_time)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Permanently gives up withdrawing from and closing the vault
+     * @dev Only callable by the vault owner and irreversible: no instruction clears the flag, it
+     * moves with the state on ownership transfer and recovery, and migrate leaves it untouched.
+     * Every owner, delegate and grantee withdrawal, panic_drain, outbound mirror syncs,
+     * migrate_out and every close fail afterwards; streams, contributor refunds and deposits
+     * keep working, so funds can only leave to recipients committed beforehand
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn renounce_withdrawals(ctx: Context<UpdateVault>, index: u64) -> Result<()> {
+        msg!("Renouncing withdrawals for vault state {}: {}", index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.withdrawals_renounced = true;
+
+        emit!(WithdrawalsRenounced {
+            user: ctx.accounts.user.key(),
+            vault_state: ctx.accounts.vault_state.key(),
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Overrides the clock the vault's time-based logic reads
+     * @dev Only compiled into builds with the test-mode feature, so locks, cooldowns and
+     * savings can be exercised without warping the validator clock; zero restores Clock::get()
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param timestamp Unix timestamp to report as the current time
+     * @return Result<()> Success or error
+     */
+    #[cfg(feature = "test-mode")]
+    pub fn test_set_clock(ctx: Context<UpdateVault>, index: u64, timestamp: i64) -> Result<()> {
+        msg!("Setting clock override to {} for vault state {}: {}", timestamp, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.clock_override = timestamp;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Overrides the vault's lifetime deposit total
+     * @dev Only compiled into builds with the test-mode feature, so overflow handling can be
+     * exercised without depositing u64::MAX lamports
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param total_deposited Lifetime deposit total to store in lamports
+     * @return Result<()> Success or error
+     */
+    #[cfg(feature = "test-mode")]
+    pub fn test_set_total_deposited(ctx: Context<UpdateVault>, index: u64, total_deposited: u64) -> Result<()> {
+        msg!("Setting total deposited to {} for vault state {}: {}", total_deposited, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.total_deposited = total_deposited;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the vault's display name
+     * @dev Only callable by the vault owner; an all-zero label removes the name
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param label Zero-padded display name
+     * @return Result<()> Success or error
+     */
+    pub fn set_label(ctx: Context<UpdateVault>, index: u64, label: [u8; LABEL_LEN]) -> Result<()> {
+        msg!("Setting label for vault state {}: {}", index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.label = label;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Adds a short categorization tag to the vault
+     * @dev Only callable by the vault owner; tags are zero-padded into fixed slots so
+     * clients can filter vaults with memcmp on the account data
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param tag Tag of 1 to TAG_LEN bytes
+     * @return Result<()> Success or error
+     */
+    pub fn add_tag(ctx: Context<UpdateVault>, index: u64, tag: String) -> Result<()> {
+        msg!("Adding tag {} to vault state {}: {}", tag, index, ctx.accounts.vault_state.key());
+        let tag = VaultState::encode_tag(&tag)?;
+        let vault_state = &mut ctx.accounts.vault_state;
+        require!(!vault_state.tags.contains(&tag), VaultError::DuplicateTag);
+
+        let slot = vault_state
+            .tags
+            .iter_mut()
+            .find(|slot| **slot == [0; TAG_LEN])
+            .ok_or(VaultError::TooManyTags)?;
+        *slot = tag;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Removes a tag from the vault
+     * @dev Only callable by the vault owner; the freed slot is reused by the next add_tag
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param tag Tag to remove
+     * @return Result<()> Success or error
+     */
+    pub fn remove_tag(ctx: Context<UpdateVault>, index: u64, tag: String) -> Result<()> {
+        msg!("Removing tag {} from vault state {}: {}", tag, index, ctx.accounts.vault_state.key());
+        let tag = VaultState::encode_tag(&tag)?;
+
+        let slot = ctx
+            .accounts
+            .vault_state
+            .tags
+            .iter_mut()
+            .find(|slot| **slot == tag)
+            .ok_or(VaultError::TagNotFound)?;
+        *slot = [0; TAG_LEN];
+
+        Ok(())
+    }
+
+    /**
+     * @notice Requires withdrawals to follow a deposit made within the last deposit_recency seconds
+     * @dev Only callable by the vault owner; zero disables the check
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param deposit_recency Maximum age of the last deposit in seconds
+     * @return Result<()> Success or error
+     */
+    pub fn set_deposit_recency(ctx: Context<UpdateVault>, index: u64, deposit_recency: i64) -> Result<()> {
+        require!(deposit_recency >= 0, VaultError::InvalidDepositRecency);
+
+        msg!("Setting deposit recency to {}s for vault state {}: {}", deposit_recency, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.deposit_recency = deposit_recency;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Caps the total the owner can withdraw per UTC day
+     * @dev Only callable by the vault owner; zero removes the cap. A tighter limit applies at once
+     * and cancels a pending change, while raising or removing the limit only applies after
+     * DAILY_LIMIT_DELAY, so a compromised key cannot lift the cap and empty the vault in one go
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param limit Maximum lamports withdrawn per day
+     * @return Result<()> Success or error
+     */
+    pub fn set_daily_limit(ctx: Context<UpdateVault>, index: u64, limit: u64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.settle_daily_limit(now);
+
+        let current = vault_state.daily_limit;
+        if limit == current || (limit != 0 && (current == 0 || limit < current)) {
+            msg!("Setting daily limit to {} lamports for vault state {}: {}", limit, index, vault_state.key());
+            vault_state.daily_limit = limit;
+            vault_state.pending_daily_limit = 0;
+            vault_state.daily_limit_change_at = 0;
+        } else {
+            let change_at = now.saturating_add(DAILY_LIMIT_DELAY);
+            msg!(
+                "Daily limit of vault state {}: {} becomes {} lamports at {}",
+                index,
+                vault_state.key(),
+                limit,
+                change_at
+            );
+            vault_state.pending_daily_limit = limit;
+            vault_state.daily_limit_change_at = change_at;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Caps how many withdrawals the vault allows per hour
+     * @dev Only callable by the vault owner; zero removes the cap. The window starts at the
+     * first withdrawal after the previous one expired
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param max Maximum withdrawals per WITHDRAWAL_WINDOW
+     * @return Result<()> Success or error
+     */
+    pub fn set_max_withdrawals_per_window(ctx: Context<UpdateVault>, index: u64, max: u32) -> Result<()> {
+        msg!(
+            "Setting max withdrawals per window to {} for vault state {}: {}",
+            max,
+            index,
+            ctx.accounts.vault_state.key()
+        );
+        ctx.accounts.vault_state.max_withdrawals_per_window = max;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Caps how much the vault can hold, to prevent over-funding a fixed goal
+     * @dev Only callable by the vault owner; the cap excludes the rent-exempt minimum seeded at
+     * initialization, and zero removes it
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param max Maximum balance above rent in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_max_balance(ctx: Context<UpdateVault>, index: u64, max: u64) -> Result<()> {
+        msg!("Setting max balance to {} lamports for vault state {}: {}", max, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.max_balance = max;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets a savings goal that keeps the vault locked until its balance reaches it
+     * @dev Only callable by the vault owner; the goal excludes the rent-exempt minimum and zero
+     * removes it. Withdrawals, spend_granted, outbound mirror syncs, migrate_out and close all
+     * honour it. Setting a goal starts it over, so GoalReached fires again on the deposit that
+     * reaches it
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Goal balance above rent in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_goal(ctx: Context<UpdateVault>, index: u64, amount: u64) -> Result<()> {
+        msg!("Setting savings goal to {} lamports for vault state {}: {}", amount, index, ctx.accounts.vault_state.key());
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.goal_amount = amount;
+        vault_state.goal_reached = false;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Makes withdrawals keep a floor that grows with the owner's lifetime deposits
+     * @dev Only callable by the vault owner; the floor is total_deposited * floor_bps /
+     * BPS_DENOMINATOR capped at floor_cap, kept on top of rent and committed funds. A zero
+     * floor_bps disables it and a zero floor_cap leaves it uncapped
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param floor_bps Share of lifetime deposits to keep in basis points
+     * @param floor_cap Maximum floor in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_balance_floor(ctx: Context<UpdateVault>, index: u64, floor_bps: u16, floor_cap: u64) -> Result<()> {
+        require!(floor_bps <= BPS_DENOMINATOR, VaultError::InvalidPercent);
+
+        msg!("Setting balance floor to {} bps capped at {} lamports for vault state {}: {}", floor_bps, floor_cap, index, ctx.accounts.vault_state.key());
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.floor_bps = floor_bps;
+        vault_state.floor_cap = floor_cap;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Holds back a fixed amount that withdrawals can never take
+     * @dev Only callable by the vault owner; the reserve is kept on top of rent and committed
+     * funds, adds to the balance floor and stays in place after withdrawals. Zero removes it
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param reserve Reserve in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_reserve(ctx: Context<UpdateVault>, index: u64, reserve: u64) -> Result<()> {
+        msg!("Setting reserve to {} lamports for vault state {}: {}", reserve, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.reserve = reserve;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Caps the total the vault can ever release to owner withdrawals
+     * @dev Only callable by the vault owner; withdrawals taking total_withdrawn past the cap
+     * fail with LifetimeCapExceeded, whatever has been deposited since, and so do closes paying
+     * out more than the cap leaves. Zero means no cap; once set, the cap can only be lowered
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param cap Maximum lifetime total withdrawn in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_lifetime_cap(ctx: Context<UpdateVault>, index: u64, cap: u64) -> Result<()> {
+        let current = ctx.accounts.vault_state.lifetime_withdraw_cap;
+        require!(
+            current == 0 || (cap != 0 && cap <= current),
+            VaultError::LifetimeCapLocked
+        );
+
+        msg!("Setting lifetime withdrawal cap to {} lamports for vault state {}: {}", cap, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.lifetime_withdraw_cap = cap;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the address panic_drain sends the vault's funds to
+     * @dev Only callable by the vault owner; the address is only trusted once delay seconds
+     * have passed, and a change can never shorten the delay already in place
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param safe_address Address receiving drained funds (default pubkey disables panic_drain)
+     * @param delay Seconds before the address is trusted
+     * @return Result<()> Success or error
+     */
+    pub fn set_safe_address(ctx: Context<UpdateVault>, index: u64, safe_address: Pubkey, delay: i64) -> Result<()> {
+        require!(delay >= 0, VaultError::InvalidSafeAddressDelay);
+
+        let now = ctx.accounts.vault_state.checked_now()?;
+        let vault_state = &mut ctx.accounts.vault_state;
+
+        msg!("Setting safe address to {} for vault state {}: {}", safe_address, index, vault_state.key());
+        vault_state.safe_address_delay = vault_state.safe_address_delay.max(delay);
+        vault_state.safe_address = safe_address;
+        vault_state.safe_address_set_at = now;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets how many deposited lamports earn one reward point
+     * @dev Only callable by the vault owner; zero stops accrual, points already earned are kept
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param lamports_per_point Lamports deposited per reward point
+     * @return Result<()> Success or error
+     */
+    pub fn set_points_rate(ctx: Context<UpdateVault>, index: u64, lamports_per_point: u64) -> Result<()> {
+        msg!("Setting points rate to 1 point per {} lamports for vault state {}: {}", lamports_per_point, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.lamports_per_point = lamports_per_point;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the lifetime deposit total above which the vault is reported for compliance
+     * @dev Only callable by the vault owner; zero disables reporting, and a new threshold
+     * can be reported again once crossed
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param reporting_threshold Lifetime deposit total in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn set_reporting_threshold(ctx: Context<UpdateVault>, index: u64, reporting_threshold: u64) -> Result<()> {
+        msg!("Setting reporting threshold to {} lamports for vault state {}: {}", reporting_threshold, index, ctx.accounts.vault_state.key());
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.reporting_threshold = reporting_threshold;
+        vault_state.threshold_reported = false;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Pauses individual instructions on the vault
+     * @dev Only callable by the vault owner; each set bit disables one instruction group,
+     * see the VaultState::PAUSE_* constants, and zero resumes everything
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param mask Bitmask of paused instructions
+     * @return Result<()> Success or error
+     */
+    pub fn set_paused_mask(ctx: Context<UpdateVault>, index: u64, mask: u8) -> Result<()> {
+        msg!("Setting paused instruction mask to {:#010b} for vault state {}: {}", mask, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.paused_instructions = mask;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Switches deposit and withdraw events between the full and compact forms
+     * @dev Only callable by the vault owner; compact events carry just a kind tag and the amount
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param enabled Whether to emit CompactEvent instead of the full events
+     * @return Result<()> Success or error
+     */
+    pub fn set_compact_events(ctx: Context<UpdateVault>, index: u64, enabled: bool) -> Result<()> {
+        msg!("Setting compact events to {} for vault state {}: {}", enabled, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.compact_events = enabled;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Bounds the clock timestamp accepted by time-sensitive instructions
+     * @dev Only callable by the vault owner; once set, streams, funding campaigns, withdraw,
+     * sync_mirror, migrate_out and close fail unless created_at <= now <= max_timestamp
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param max_timestamp Latest plausible unix timestamp, or 0 to disable the check
+     * @return Result<()> Success or error
+     */
+    pub fn set_clock_bound(ctx: Context<UpdateVault>, index: u64, max_timestamp: i64) -> Result<()> {
+        require!(
+            max_timestamp == 0 || max_timestamp > ctx.accounts.vault_state.created_at,
+            VaultError::InvalidClockBound
+        );
+
+        msg!("Setting maximum timestamp to {} for vault state {}: {}", max_timestamp, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.max_timestamp = max_timestamp;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the emergency contact and the heuristics that flag suspicious withdrawals
+     * @dev Only callable by the vault owner; a default contact disables monitoring, and a zero
+     * threshold disables the corresponding heuristic
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param contact Account named in SuspiciousActivity events for off-chain watchers
+     * @param large_withdrawal_bps Flags a withdrawal above this share of the balance in basis points
+     * @param rapid_withdrawal_count Flags this many withdrawals within the rapid window
+     * @param rapid_window Length of the rapid withdrawal window in seconds
+     * @return Result<()> Success or error
+     */
+    pub fn set_emergency_contact(
+        ctx: Context<UpdateVault>,
+        index: u64,
+        contact: Pubkey,
+        large_withdrawal_bps: u16,
+        rapid_withdrawal_count: u8,
+        rapid_window: i64,
+    ) -> Result<()> {
+        require!(large_withdrawal_bps <= BPS_DENOMINATOR, VaultError::InvalidMonitorConfig);
+        require!(
+            rapid_withdrawal_count == 0 || rapid_window > 0,
+            VaultError::InvalidMonitorConfig
+        );
+
+        msg!("Setting emergency contact to {} for vault state {}: {}", contact, index, ctx.accounts.vault_state.key());
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.emergency_contact = contact;
+        vault_state.large_withdrawal_bps = large_withdrawal_bps;
+        vault_state.rapid_withdrawal_count = rapid_withdrawal_count;
+        vault_state.rapid_window = rapid_window;
+        vault_state.rapid_window_start = 0;
+        vault_state.rapid_window_withdrawals = 0;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Returns a summary of every limit currently enforced on the vault
+     * @dev Read-only; the result is written via set_return_data
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<VaultConstraints> Active constraints or error
+     */
+    pub fn get_constraints(ctx: Context<ViewVault>, index: u64) -> Result<VaultConstraints> {
+        msg!("Reading constraints of vault {}: {}", index, ctx.accounts.vault.key());
+        ctx.accounts.constraints()
+    }
+
+    /**
+     * @notice Returns the vault's PDAs, their bump seeds and its balance
+     * @dev Read-only; the result is written via set_return_data. The accounts only resolve when
+     * the client derived them with the same seeds, so the result confirms the derivation
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<VaultInfo> Addresses, bumps and balance of the vault
+     */
+    pub fn get_vault_info(ctx: Context<ViewVault>, index: u64) -> Result<VaultInfo> {
+        msg!("Reading info of vault {}: {}", index, ctx.accounts.vault.key());
+        Ok(ctx.accounts.info())
+    }
+
+    /**
+     * @notice Returns a chunk of the serialized vault state for programs that read it over CPI
+     * @dev Read-only; the result is written via set_return_data. The state exceeds the return data
+     * limit, so callers request chunks from offset 0 until total_len bytes are collected. The bytes
+     * match the account data, discriminator included, so VaultState::try_deserialize decodes them
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @param offset Byte offset of the first byte to return
+     * @return Result<StateChunk> Requested bytes or InvalidStateOffset
+     */
+    pub fn read_state(ctx: Context<ViewVault>, index: u64, offset: u32) -> Result<StateChunk> {
+        msg!("Reading state of vault {}: {} from offset {}", index, ctx.accounts.vault.key(), offset);
+        ctx.accounts.state_chunk(offset)
+    }
+
+    /**
+     * @notice Returns how much can currently be withdrawn from the vault
+     * @dev Read-only; the result is written via set_return_data. Only the balance is considered,
+     * so per-withdrawal limits, locks and pauses still apply (see check_rate_limits)
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<u64> Balance above rent, committed funds, the balance floor and locked savings
+     */
+    pub fn get_withdrawable(ctx: Context<ViewVault>, index: u64) -> Result<u64> {
+        msg!("Reading withdrawable balance of vault {}: {}", index, ctx.accounts.vault.key());
+        let now = ctx.accounts.vault_state.now()?;
+        ctx.accounts.withdrawable(now)
+    }
+
+    /**
+     * @notice Returns a hash of every owner-configurable setting for change detection
+     * @dev Read-only; the result is written via set_return_data
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<[u8; 32]> SHA-256 of the configuration fields in a stable order
+     */
+    pub fn config_hash(ctx: Context<ViewVault>, index: u64) -> Result<[u8; 32]> {
+        msg!("Hashing configuration of vault state {}: {}", index, ctx.accounts.vault_state.key());
+        Ok(ctx.accounts.vault_state.config_hash())
+    }
+
+    /**
+     * @notice Returns an at-a-glance health score for the vault from 0 to 100
+     * @dev Read-only; the score is derived from stored fields and the live balance, see
+     * ViewVault::health_score for the formula
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<u8> Health score or error
+     */
+    pub fn health_score(ctx: Context<ViewVault>, index: u64) -> Result<u8> {
+        msg!("Scoring health of vault {}: {}", index, ctx.accounts.vault.key());
+        ctx.accounts.health_score()
+    }
+
+    /**
+     * @notice Emits a checkpoint of the complete vault state for offline reconciliation
+     * @dev Read-only; auditors compare it against state rebuilt from incremental events
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn emit_reconciliation(ctx: Context<ViewVault>, index: u64) -> Result<()> {
+        msg!("Emitting reconciliation checkpoint for vault {}: {}", index, ctx.accounts.vault.key());
+        let vault_balance = ctx.accounts.vault.get_lamports();
+        let rent_exempt = Rent::get()?.minimum_balance(ctx.accounts.vault.to_account_info().data_len());
+
+        emit!(FullStateEvent {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            vault_state: ctx.accounts.vault_state.key(),
+            balance: vault_balance,
+            rent_exempt,
+            state: (*ctx.accounts.vault_state).clone(),
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Links the vault as a mirror of another vault
+     * @dev Only callable by the vault owner; the default pubkey removes the link
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param mirror_of Vault account whose balance this vault should mirror
+     * @return Result<()> Success or error
+     */
+    pub fn set_mirror(ctx: Context<UpdateVault>, index: u64, mirror_of: Pubkey) -> Result<()> {
+        msg!("Setting mirror of vault state {}: {} to {}", index, ctx.accounts.vault_state.key(), mirror_of);
+        ctx.accounts.vault_state.mirror_of = mirror_of;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Syncs the vault balance to match the mirrored vault
+     * @dev Handles both directions: tops up from the owner or returns the excess to the owner
+     * @param ctx SyncMirror context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn sync_mirror(ctx: Context<SyncMirror>, index: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_MIRROR)?;
+        msg!("Syncing vault {}: {} to mirrored vault: {}", index, ctx.accounts.vault.key(), ctx.accounts.mirrored_vault.key());
+        let (previous_balance, fee) = ctx.accounts.sync(ctx.remaining_accounts)?;
+
+        emit!(MirrorSynced {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            mirrored_vault: ctx.accounts.mirrored_vault.key(),
+            previous_balance,
+            new_balance: ctx.accounts.vault.get_lamports(),
+            fee,
+        });
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Configures the approvers required for large withdrawals
+     * @dev Only callable by the vault owner; withdrawals above the threshold need
+     * signatures from at least required_approvals distinct approvers
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param approvers Set of approver keys (at most MAX_APPROVERS, empty disables approvals)
+     * @param required_approvals Number of distinct approver signatures required (N of M)
+     * @param approval_threshold Withdrawals above this amount require approvals
+     * @return Result<()> Success or error
+     */
+    pub fn set_approvers(
+        ctx: Context<UpdateVault>,
+        index: u64,
+        approvers: Vec<Pubkey>,
+        required_approvals: u8,
+        approval_threshold: u64,
+    ) -> Result<()> {
+        require!(approvers.len() <= VaultState::MAX_APPROVERS, VaultError::TooManyApprovers);
+        require!(
+            usize::from(required_approvals) <= approvers.len(),
+            VaultError::InvalidApprovalConfig
+        );
+        require!(
+            approvers.is_empty() || required_approvals > 0,
+            VaultError::InvalidApprovalConfig
+        );
+
+        for (i, approver) in approvers.iter().enumerate() {
+            require!(*approver != Pubkey::default(), VaultError::InvalidApprovalConfig);
+            require!(!approvers[..i].contains(approver), VaultError::InvalidApprovalConfig);
+        }
+
+        msg!(
+            "Setting {}-of-{} approvals above {} lamports for vault state {}: {}",
+            required_approvals,
+            approvers.len(),
+            approval_threshold,
+            index,
+            ctx.accounts.vault_state.key()
+        );
+
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.approvers = [Pubkey::default(); VaultState::MAX_APPROVERS];
+        vault_state.approvers[..approvers.len()].copy_from_slice(&approvers);
+        vault_state.required_approvals = required_approvals;
+        vault_state.approval_threshold = approval_threshold;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Configures the beneficiaries that share the vault balance when it is closed
+     * @dev Only callable by the vault owner; shares must sum to BPS_DENOMINATOR, and an empty
+     * list restores paying everything to the owner
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param beneficiaries Beneficiaries and their shares (at most MAX_BENEFICIARIES)
+     * @return Result<()> Success or error
+     */
+    pub fn set_beneficiaries(ctx: Context<UpdateVault>, index: u64, beneficiaries: Vec<BeneficiaryShare>) -> Result<()> {
+        require!(
+            beneficiaries.len() <= VaultState::MAX_BENEFICIARIES,
+            VaultError::TooManyBeneficiaries
+        );
+
+        let mut total_bps: u32 = 0;
+        for (i, share) in beneficiaries.iter().enumerate() {
+            require!(
+                share.beneficiary != Pubkey::default() && share.share_bps > 0,
+                VaultError::InvalidBeneficiaryShares
+            );
+            require!(
+                !beneficiaries[..i].iter().any(|other| other.beneficiary == share.beneficiary),
+                VaultError::InvalidBeneficiaryShares
+            );
+            total_bps += u32::from(share.share_bps);
+        }
+        require!(
+            beneficiaries.is_empty() || total_bps == u32::from(BPS_DENOMINATOR),
+            VaultError::InvalidBeneficiaryShares
+        );
+
+        msg!(
+            "Setting {} beneficiaries for vault state {}: {}",
+            beneficiaries.len(),
+            index,
+            ctx.accounts.vault_state.key()
+        );
+
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.beneficiaries = [BeneficiaryShare::default(); VaultState::MAX_BENEFICIARIES];
+        vault_state.beneficiaries[..beneficiaries.len()].copy_from_slice(&beneficiaries);
+
+        Ok(())
+    }
+
+    /**
+     * @notice Creates a stream that deposits funds which unlock continuously for a recipient
+     * @dev Deposits the full total up front; the recipient claims rate_per_sec * elapsed over time
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @param total Total amount to stream in lamports
+     * @param rate_per_sec Amount that vests each second in lamports
+     * @param recipient Account allowed to claim the vested funds
+     * @return Result<()> Success or error
+     */
+    pub fn create_stream(ctx: Context<Payment>, index: u64, total: u64, rate_per_sec: u64, recipient: Pubkey) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_STREAM)?;
+        require!(rate_per_sec > 0, VaultError::InvalidStreamParams);
+        require!(recipient != Pubkey::default(), VaultError::InvalidStreamParams);
+        require!(!ctx.accounts.vault_state.has_active_stream(), VaultError::StreamAlreadyActive);
+
+        msg!("Creating stream of {} lamports at {} lamports/s from vault {} to: {}", total, rate_per_sec, index, recipient);
+        ctx.accounts.deposit(total)?;
+        ctx.accounts.report_threshold_crossing();
+
+        let start = ctx.accounts.vault_state.checked_now()?;
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.stream_recipient = recipient;
+        vault_state.stream_total = total;
+        vault_state.stream_rate = rate_per_sec;
+        vault_state.stream_start = start;
+        vault_state.stream_claimed = 0;
+
+        emit!(StreamCreated {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            recipient,
+            total,
+            rate_per_sec,
+            start,
+        });
+        ctx.accounts.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Claims the funds that have vested in the stream so far
+     * @dev Only callable by the stream recipient; the stream is cleared once fully claimed
+     * @param ctx ClaimStream context
+     * @param index Index of the vault among the owner's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn claim_stream(ctx: Context<ClaimStream>, index: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_STREAM)?;
+        let now = ctx.accounts.vault_state.checked_now()?;
+        let amount = ctx.accounts.vault_state.stream_claimable(now);
+        require!(amount > 0, VaultError::NothingToClaim);
+
+        msg!("Claiming {} lamports from stream on vault {}: {}", amount, index, ctx.accounts.vault.key());
+        ctx.accounts.claim(amount)?;
+
+        emit!(StreamClaimed {
+            vault: ctx.accounts.vault.key(),
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            claimed_total: ctx.accounts.vault_state.stream_claimed,
+        });
+
+        // A fully claimed stream has run dry and frees the slot for a new one
+        if ctx.accounts.vault_state.stream_claimed == ctx.accounts.vault_state.stream_total {
+            ctx.accounts.vault_state.clear_stream();
+        }
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Cancels the active stream and splits the remaining funds
+     * @dev Only callable by the stream creator; the vested portion goes to the recipient and the unvested remainder back to the creator
+     * @param ctx CancelStream context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn cancel_stream(ctx: Context<CancelStream>, index: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_STREAM)?;
+        msg!("Cancelling stream on vault {}: {}", index, ctx.accounts.vault.key());
+        let (vested, returned) = ctx.accounts.cancel()?;
+
+        emit!(StreamCancelled {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            recipient: ctx.accounts.recipient.key(),
+            vested,
+            returned,
+        });
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Starts an all-or-nothing funding campaign for the vault
+     * @dev Only callable by the owner; contributions are refundable unless the goal is met by the deadline
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param funding_goal Total contributions required in lamports
+     * @param deadline Unix timestamp at which the campaign ends
+     * @return Result<()> Success or error
+     */
+    pub fn set_funding_goal(ctx: Context<UpdateVault>, index: u64, funding_goal: u64, deadline: i64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(funding_goal > 0, VaultError::InvalidFundingParams);
+        require!(deadline > now, VaultError::InvalidFundingParams);
+        require!(
+            !ctx.accounts.vault_state.has_active_campaign(now),
+            VaultError::FundingCampaignActive
+        );
+
+        msg!("Setting funding goal of {} lamports until {} for vault state {}: {}", funding_goal, deadline, index, ctx.accounts.vault_state.key());
+
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.funding_goal = funding_goal;
+        vault_state.funding_deadline = deadline;
+        vault_state.funding_raised = 0;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Contributes funds towards the vault's funding goal
+     * @dev Callable by anyone before the deadline; each contributor's total is tracked for refunds
+     * @param ctx Contribute context
+     * @param index Index of the vault among the owner's vaults
+     * @param amount Amount to contribute in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn contribute(ctx: Context<Contribute>, index: u64, amount: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_CONTRIBUTE)?;
+        ctx.accounts.vault_state.require_min_deposit(amount)?;
+
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(ctx.accounts.vault_state.funding_goal > 0, VaultError::NoFundingCampaign);
+        require!(now < ctx.accounts.vault_state.funding_deadline, VaultError::FundingClosed);
+
+        msg!("Contributing {} lamports to vault {}: {}", amount, index, ctx.accounts.vault.key());
+        ctx.accounts.contribute(amount, ctx.bumps.contribution)?;
+
+        emit!(ContributionReceived {
+            contributor: ctx.accounts.contributor.key(),
+            vault: ctx.accounts.vault.key(),
+            amount,
+            total_raised: ctx.accounts.vault_state.funding_raised,
+        });
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Refunds a contributor after a campaign missed its goal
+     * @dev Only possible once the deadline has passed without the goal being met
+     * @param ctx Refund context
+     * @param index Index of the vault among the owner's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn refund(ctx: Context<Refund>, index: u64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(now >= ctx.accounts.vault_state.funding_deadline, VaultError::DeadlineNotPassed);
+        require!(!ctx.accounts.vault_state.funding_succeeded(now), VaultError::GoalMetNoRefund);
+
+        let amount = ctx.accounts.contribution.amount;
+        msg!("Refunding {} lamports from vault {}: {}", amount, index, ctx.accounts.vault.key());
+        ctx.accounts.refund(amount)?;
+
+        emit!(ContributionRefunded {
+            contributor: ctx.accounts.contributor.key(),
+            vault: ctx.accounts.vault.key(),
+            amount,
+        });
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Returns the vault's inflow and outflow for each of the last FLOW_DAYS days
+     * @dev Read-only; entries are ordered oldest to newest and days without activity are zero
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<Vec<FlowBucket>> Daily flow buckets or error
+     */
+    pub fn get_flows(ctx: Context<ViewVault>, index: u64) -> Result<Vec<FlowBucket>> {
+        msg!("Reading flows of vault {}: {}", index, ctx.accounts.vault.key());
+        Ok(ctx.accounts.vault_state.recent_flows(ctx.accounts.vault_state.now()?))
+    }
+
+    /**
+     * @notice Reports which withdrawal limits a withdrawal of amount would violate right now
+     * @dev Read-only preflight for UIs; mirrors the checks performed by withdraw
+     * @param ctx ViewVault context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount the client intends to withdraw in lamports
+     * @return Result<RateLimitStatus> Status of each limit or error
+     */
+    pub fn check_rate_limits(ctx: Context<ViewVault>, index: u64, amount: u64) -> Result<RateLimitStatus> {
+        msg!("Checking rate limits of vault {}: {} for {} lamports", index, ctx.accounts.vault.key(), amount);
+        ctx.accounts.rate_limit_status(amount)
+    }
+
+    /**
+     * @notice Reports whether key may currently perform action on the vault
+     * @dev Read-only; mirrors the signer and pause gating of each handler, but not amount-based
+     * checks or approver co-signatures. Pass the key's spending grant to account for it
+     * @param ctx AuthorizationQuery context
+     * @param index Index of the vault among the user's vaults
+     * @param key Account whose authorization is queried
+     * @param action One of the VaultState::ACTION_* codes
+     * @return Result<bool> Whether the action is authorized, or InvalidAction
+     */
+    pub fn is_authorized(ctx: Context<AuthorizationQuery>, index: u64, key: Pubkey, action: u8) -> Result<bool> {
+        msg!("Checking action {} for {} on vault state {}: {}", action, key, index, ctx.accounts.vault_state.key());
+        ctx.accounts.is_authorized(key, action)
+    }
+
+    /**
+     * @notice Converts an amount between two decimal scales
+     * @dev Pure helper written via set_return_data so every SDK shares one rounding rule:
+     * scaling down truncates toward zero and scaling up fails instead of overflowing
+     * @param ctx Stateless context
+     * @param amount Amount expressed with from_decimals decimals
+     * @param from_decimals Decimals of the input amount
+     * @param to_decimals Decimals of the returned amount
+     * @return Result<u64> Converted amount or ConversionOverflow
+     */
+    pub fn convert_units(_ctx: Context<Stateless>, amount: u64, from_decimals: u8, to_decimals: u8) -> Result<u64> {
+        let scale = |diff: u8| 10u64.checked_pow(u32::from(diff)).ok_or(VaultError::ConversionOverflow);
+
+        if to_decimals >= from_decimals {
+            amount
+                .checked_mul(scale(to_decimals - from_decimals)?)
+                .ok_or_else(|| VaultError::ConversionOverflow.into())
+        } else {
+            // Past 10^19 every u64 truncates to zero
+            Ok(scale(from_decimals - to_decimals).map_or(0, |divisor| amount / divisor))
+        }
+    }
+
+    /**
+     * @notice Records an access attempt against the vault in its access log
+     * @dev Callable by anyone; the vault itself is not modified and the oldest entry is overwritten once the log is full
+     * @param ctx LogAccess context
+     * @param index Index of the vault among the owner's vaults
+     * @param purpose Short description of why the vault was accessed
+     * @return Result<()> Success or error
+     */
+    pub fn log_access(ctx: Context<LogAccess>, index: u64, purpose: String) -> Result<()> {
+        require!(purpose.len() <= MAX_PURPOSE_LEN, VaultError::PurposeTooLong);
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        let accessor = ctx.accounts.accessor.key();
+
+        msg!("Logging access by {} to vault state {}: {}", accessor, index, ctx.accounts.vault_state.key());
+
+        let access_log = &mut ctx.accounts.access_log;
+        access_log.vault_state = ctx.accounts.vault_state.key();
+        access_log.bump = ctx.bumps.access_log;
+        access_log.record(AccessEntry {
+            accessor,
+            timestamp,
+            purpose: purpose.clone(),
+        });
+
+        emit!(AccessLogged {
+            vault_state: ctx.accounts.vault_state.key(),
+            accessor,
+            timestamp,
+            purpose,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Returns a page of the access log, oldest entry first
+     * @dev Read-only; start indexes the stored entries in chronological order across the
+     * ring-buffer wraparound, and the page also reports the stored and lifetime entry counts
+     * @param ctx ViewAccessLog context
+     * @param index Index of the vault among the owner's vaults
+     * @param start Index of the first entry to return, 0 being the oldest stored entry
+     * @param count Maximum number of entries to return, at most MAX_LOG_PAGE
+     * @return Result<LogPage> Requested page or error
+     */
+    pub fn get_log_page(ctx: Context<ViewAccessLog>, index: u64, start: u8, count: u8) -> Result<LogPage> {
+        msg!("Reading access log of vault state {}: {} from entry {}", index, ctx.accounts.vault_state.key(), start);
+        ctx.accounts.access_log.page(start, count)
+    }
+
+    /**
+     * @notice Marks the vault for migration to a successor program
+     * @dev Only callable by the vault owner; the default pubkey cancels a prepared migration
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param target_program Program that will control the migrated funds
+     * @return Result<()> Success or error
+     */
+    pub fn prepare_migration(ctx: Context<UpdateVault>, index: u64, target_program: Pubkey) -> Result<()> {
+        require!(target_program != crate::ID, VaultError::InvalidMigrationTarget);
+        require!(target_program != System::id(), VaultError::InvalidMigrationTarget);
+
+        msg!("Preparing migration of vault state {}: {} to program: {}", index, ctx.accounts.vault_state.key(), target_program);
+        ctx.accounts.vault_state.migration_target = target_program;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Moves all vault funds to the successor program and closes the local state
+     * @dev Funds go to the target program's vault PDA derived from the same seeds, signed by this vault's PDA
+     * @param ctx MigrateOut context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn migrate_out(ctx: Context<MigrateOut>, index: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_MIGRATE)?;
+        let vault_balance = ctx.accounts.vault.get_lamports();
+
+        msg!("Migrating {} lamports from vault {}: {} to: {}", vault_balance, index, ctx.accounts.vault.key(), ctx.accounts.destination.key());
+        ctx.accounts.migrate_out()?;
+
+        emit!(VaultMigrated {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            target_program: ctx.accounts.target_program.key(),
+            destination: ctx.accounts.destination.key(),
+            amount: vault_balance,
+        });
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Upgrades a vault state created by an older program version to the current layout
+     * @dev Only callable by the vault owner, who pays the rent for any extra space. Grows the
+     * account to the current size, fills fields it lacked with their defaults and stamps
+     * VaultState::VERSION; a vault already at the current version is left untouched
+     * @param ctx MigrateState context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn migrate(ctx: Context<MigrateState>, index: u64) -> Result<()> {
+        msg!("Migrating vault {} for vault state: {}", index, ctx.accounts.vault_state.key());
+        ctx.accounts.migrate()
+    }
+
+    /**
+     * @notice Rewrites the stored bump seeds with the canonical ones
+     * @dev Only callable by the vault owner; repairs a vault state whose bumps went stale, e.g.
+     * after a migration recreated it, since every other instruction derives the PDAs from them
+     * @param ctx RefreshBumps context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn refresh_bumps(ctx: Context<RefreshBumps>, index: u64) -> Result<()> {
+        msg!(
+            "Refreshing bumps for vault state {}: {} to {} and {}",
+            index,
+            ctx.accounts.vault_state.key(),
+            ctx.bumps.vault_state,
+            ctx.bumps.vault
+        );
+        ctx.accounts.vault_state.state_bump = ctx.bumps.vault_state;
+        ctx.accounts.vault_state.vault_bump = ctx.bumps.vault;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Overwrites the stored bump seeds
+     * @dev Only compiled into builds with the test-mode feature, so refresh_bumps can be
+     * exercised against a vault state with stale bumps
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param state_bump Bump seed to store for the vault state PDA
+     * @param vault_bump Bump seed to store for the vault PDA
+     * @return Result<()> Success or error
+     */
+    #[cfg(feature = "test-mode")]
+    pub fn test_set_bumps(ctx: Context<UpdateVault>, index: u64, state_bump: u8, vault_bump: u8) -> Result<()> {
+        msg!("Setting bumps to {} and {} for vault state {}: {}", state_bump, vault_bump, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.state_bump = state_bump;
+        ctx.accounts.vault_state.vault_bump = vault_bump;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Moves every lamport out of the vault PDA, rent-exempt seed included
+     * @dev Only compiled into builds with the test-mode feature, so closing an empty vault
+     * can be exercised
+     * @param ctx Payment context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    #[cfg(feature = "test-mode")]
+    pub fn test_empty_vault(ctx: Context<Payment>, index: u64) -> Result<()> {
+        let balance = ctx.accounts.vault.get_lamports();
+
+        msg!("Emptying vault {}: {} of {} lamports", index, ctx.accounts.vault.key(), balance);
+        transfer_from_vault(
+            &ctx.accounts.system_program,
+            &ctx.accounts.vault,
+            ctx.accounts.user.to_account_info(),
+            &ctx.accounts.user.key(),
+            &ctx.accounts.vault_state,
+            balance,
+        )?;
+        ctx.accounts.global_stats.record_outflow(balance);
+
+        Ok(())
+    }
+
+    /**
+     * @notice Proposes a new authority for the vault
+     * @dev Only callable by the vault owner; nothing moves until the new authority calls
+     * accept_ownership, so a mistyped address can't take the vault. Proposing again replaces
+     * the pending authority
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param new_authority Wallet that may take over the vault
+     * @return Result<()> Success or error
+     */
+    pub fn transfer_ownership(ctx: Context<UpdateVault>, index: u64, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_MIGRATE)?;
+        require!(
+            new_authority != Pubkey::default() && new_authority != ctx.accounts.user.key(),
+            VaultError::InvalidNewAuthority
+        );
+
+        msg!("Proposing {} as the authority of vault state {}: {}", new_authority, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.pending_authority = new_authority;
+
+        emit!(OwnershipTransferInitiated {
+            owner: ctx.accounts.user.key(),
+            pending_authority: new_authority,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Takes over a vault whose owner proposed the signer as its new authority
+     * @dev The state and funds move to the PDAs seeded by the new authority, since access is
+     * derived from those seeds, and the old state is closed to the old authority. Vaults with
+     * committed funds or tied to a mint cannot be transferred, and spending grants and the
+     * access log stay with the old state
+     * @param ctx AcceptOwnership context
+     * @param index Index of the vault among the owner's vaults, kept among the new owner's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn accept_ownership(ctx: Context<AcceptOwnership>, index: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_MIGRATE)?;
+
+        msg!("Transferring vault {}: {} from {} to {}", index, ctx.accounts.vault.key(), ctx.accounts.owner.key(), ctx.accounts.new_owner.key());
+        ctx.accounts.accept_ownership(&ctx.bumps)?;
+
+        emit!(OwnershipTransferred {
+            old: ctx.accounts.owner.key(),
+            new: ctx.accounts.new_owner.key(),
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws a pending ownership transfer
+     * @dev Only callable by the vault owner
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn cancel_transfer(ctx: Context<UpdateVault>, index: u64) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.pending_authority != Pubkey::default(),
+            VaultError::NoPendingTransfer
+        );
+
+        msg!("Cancelling ownership transfer of vault state {}: {}", index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.pending_authority = Pubkey::default();
+
+        Ok(())
+    }
+
+    /**
+     * @notice Names the account that can recover the vault if the owner loses their key
+     * @dev Only callable by the vault owner; the default pubkey removes the guardian and
+     * cancels any pending recovery
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param guardian Account allowed to start a recovery
+     * @return Result<()> Success or error
+     */
+    pub fn set_guardian(ctx: Context<UpdateVault>, index: u64, guardian: Pubkey) -> Result<()> {
+        require_keys_neq!(guardian, ctx.accounts.user.key(), VaultError::InvalidGuardian);
+
+        msg!("Setting guardian to {} for vault state {}: {}", guardian, index, ctx.accounts.vault_state.key());
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.guardian = guardian;
+        if guardian == Pubkey::default() {
+            vault_state.clear_recovery();
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Starts handing the vault to a new authority on behalf of an owner who lost their key
+     * @dev Only callable by the guardian; the recovery can be finalized once RECOVERY_COOLDOWN
+     * has passed, giving the owner time to cancel it. Starting again restarts the cooldown
+     * @param ctx InitiateRecovery context
+     * @param index Index of the vault among the owner's vaults
+     * @param new_authority Wallet that will take over the vault
+     * @return Result<()> Success or error
+     */
+    pub fn initiate_recovery(ctx: Context<InitiateRecovery>, index: u64, new_authority: Pubkey) -> Result<()> {
+        require!(
+            new_authority != Pubkey::default() && new_authority != ctx.accounts.owner.key(),
+            VaultError::InvalidNewAuthority
+        );
+
+        let now = ctx.accounts.vault_state.checked_now()?;
+
+        msg!("Initiating recovery of vault state {}: {} to {}", index, ctx.accounts.vault_state.key(), new_authority);
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.recovery_authority = new_authority;
+        vault_state.recovery_initiated_at = now;
+
+        emit!(RecoveryInitiated {
+            owner: ctx.accounts.owner.key(),
+            guardian: ctx.accounts.guardian.key(),
+            new_authority,
+            finalize_after: now.saturating_add(RECOVERY_COOLDOWN),
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Cancels a pending recovery
+     * @dev Only callable by the vault owner
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn cancel_recovery(ctx: Context<UpdateVault>, index: u64) -> Result<()> {
+        require!(ctx.accounts.vault_state.has_pending_recovery(), VaultError::NoRecoveryPending);
+
+        msg!("Cancelling recovery of vault state {}: {}", index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.clear_recovery();
+
+        Ok(())
+    }
+
+    /**
+     * @notice Completes a recovery once its cooldown has passed
+     * @dev The guardian and the new authority sign; like accept_ownership, the state and funds
+     * move to the PDAs seeded by the new authority, and the old state's rent goes to the new authority
+     * @param ctx FinalizeRecovery context
+     * @param index Index of the vault among the owner's vaults, kept among the new owner's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn finalize_recovery(ctx: Context<FinalizeRecovery>, index: u64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(
+            now >= ctx.accounts.vault_state.recovery_initiated_at.saturating_add(RECOVERY_COOLDOWN),
+            VaultError::RecoveryCooldownActive
+        );
+
+        msg!("Recovering vault {}: {} from {} to {}", index, ctx.accounts.vault.key(), ctx.accounts.owner.key(), ctx.accounts.new_owner.key());
+        ctx.accounts.finalize_recovery(&ctx.bumps)?;
+
+        emit!(OwnershipTransferred {
+            old: ctx.accounts.owner.key(),
+            new: ctx.accounts.new_owner.key(),
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Authorizes a grantee to withdraw up to a cumulative ceiling until an expiry
+     * @dev Only callable by the vault owner; granting again to the same grantee replaces the grant
+     * @param ctx GrantSpending context
+     * @param index Index of the vault among the user's vaults
+     * @param grantee Account allowed to spend from the vault
+     * @param ceiling Maximum cumulative amount the grantee may withdraw in lamports
+     * @param expires_at Unix timestamp after which the grant can no longer be used
+     * @return Result<()> Success or error
+     */
+    pub fn grant_spending_authority(
+        ctx: Context<GrantSpending>,
+        index: u64,
+        grantee: Pubkey,
+        ceiling: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(ceiling > 0, VaultError::InvalidGrantParams);
+        require!(expires_at > now, VaultError::InvalidGrantParams);
+
+        msg!("Granting {} lamports of spending from vault {} to {} until {}", ceiling, index, grantee, expires_at);
+        ctx.accounts.grant.set_inner(SpendingGrant {
+            grantee,
+            ceiling,
+            spent: 0,
+            expires_at,
+            bump: ctx.bumps.grant,
+        });
+
+        emit!(SpendingGranted {
+            user: ctx.accounts.user.key(),
+            vault_state: ctx.accounts.vault_state.key(),
+            grantee,
+            ceiling,
+            expires_at,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws funds to the grantee against an unexpired spending grant
+     * @dev Subject to every withdraw check and the withdrawal fee, and counted against the daily,
+     * window and lifetime limits like any owner withdrawal; the grant is closed and its rent returned to the owner once the ceiling is exhausted
+     * @param ctx SpendGranted context
+     * @param index Index of the vault among the owner's vaults
+     * @param amount Amount to withdraw in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn spend_granted(ctx: Context<SpendGranted>, index: u64, amount: u64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(now < ctx.accounts.grant.expires_at, VaultError::GrantExpired);
+
+        let remaining = ctx
+            .accounts
+            .grant
+            .ceiling
+            .checked_sub(ctx.accounts.grant.spent)
+            .ok_or(VaultError::MathOverflow)?;
+        require!(amount <= remaining, VaultError::GrantCeilingExceeded);
+
+        let vault = &ctx.accounts.vault;
+        ctx.accounts.vault_state.check_withdrawal(vault, amount, Some(ctx.remaining_accounts))?;
+
+        msg!("Grantee {} spending {} lamports from vault {}: {}", ctx.accounts.grantee.key(), amount, index, ctx.accounts.vault.key());
+        let fee = ctx.accounts.spend(amount, now)?;
+
+        emit!(GrantSpent {
+            grantee: ctx.accounts.grantee.key(),
+            vault: ctx.accounts.vault.key(),
+            amount,
+            fee,
+            remaining: remaining.checked_sub(amount).ok_or(VaultError::MathOverflow)?,
+        });
+
+        if remaining == amount {
+            msg!("Spending grant exhausted, revoking");
+            ctx.accounts.grant.close(ctx.accounts.owner.to_account_info())?;
+        }
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Ties the vault to an SPL mint and creates its token account
+     * @dev Only callable by the vault owner, once; the token account is the vault PDA's
+     * associated token account, and the native SOL balance is unaffected
+     * @param ctx InitializeToken context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn initialize_token(ctx: Context<InitializeToken>, index: u64) -> Result<()> {
+        require!(
+            ctx.accounts.vault_state.mint == Pubkey::default(),
+            VaultError::TokenVaultAlreadyInitialized
+        );
+
+        msg!("Tying vault {}: {} to mint: {}", index, ctx.accounts.vault.key(), ctx.accounts.mint.key());
+        ctx.accounts.vault_state.mint = ctx.accounts.mint.key();
+
+        Ok(())
+    }
+
+    /**
+     * @notice Deposits SPL tokens from the user's token account into the vault
+     * @param ctx TokenPayment context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to deposit in base units of the mint
+     * @return Result<()> Success or error
+     */
+    pub fn deposit_token(ctx: Context<TokenPayment>, index: u64, amount: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        require!(amount > 0, VaultError::InsufficientDepositAmount);
+
+        msg!("Depositing {} tokens to vault {}: {}", amount, index, ctx.accounts.vault.key());
+        ctx.accounts.deposit(amount)?;
+
+        emit!(TokenDeposited {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            mint: ctx.accounts.vault_state.mint,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Withdraws SPL tokens from the vault to the user's token account
+     * @dev Honours the pause, renounce, time lock and approval settings like withdraw; the
+     * lamport-denominated goal, min/max withdrawal and daily/window limits, and activity
+     * monitoring, do not apply to token amounts
+     * @param ctx TokenPayment context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to withdraw in base units of the mint
+     * @return Result<()> Success or error
+     */
+    pub fn withdraw_token(ctx: Context<TokenPayment>, index: u64, amount: u64) -> Result<()> {
+        ctx.accounts.vault_state.require_not_paused(VaultState::PAUSE_WITHDRAW)?;
+        ctx.accounts.vault_state.require_not_renounced()?;
+        require!(amount > 0, VaultError::InvalidWithdrawAmount);
+
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(!ctx.accounts.vault_state.is_locked(now), VaultError::VaultLocked);
+
+        ctx.accounts.vault_state.require_approvals(amount, ctx.remaining_accounts)?;
+
+        msg!("Withdrawing {} tokens from vault {}: {}", amount, index, ctx.accounts.vault.key());
+        ctx.accounts.withdraw(amount)?;
+
+        emit!(TokenWithdrawn {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            mint: ctx.accounts.vault_state.mint,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Deposits wrapped SOL from the user's token account as native lamports
+     * @dev Moves the wSOL into a temporary vault-owned token account and closes it to the user,
+     * which unwraps it, then runs a regular deposit of the same amount
+     * @param ctx DepositWsol context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount of wSOL to deposit, in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn deposit_wsol(ctx: Context<DepositWsol>, index: u64, amount: u64) -> Result<()> {
+        ctx.accounts.payment.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        ctx.accounts.payment.vault_state.require_direct_deposit(amount)?;
+
+        msg!("Unwrapping {} lamports of wSOL into vault {}: {}", amount, index, ctx.accounts.payment.vault.key());
+        ctx.accounts.unwrap(amount)?;
+
+        let payment = &mut ctx.accounts.payment;
+        payment.deposit(amount)?;
+        payment.report_threshold_crossing();
+
+        let points_earned = payment.accrue_points(amount)?;
+        payment.emit_deposited(amount, points_earned, 1, [0; MEMO_LEN])?;
+        payment.assert_invariants()?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Immediately moves the whole withdrawable balance to the vault's safe address
+     * @dev Only callable by the vault owner; bypasses locks, limits, approvals and pauses,
+     * but leaves rent and funds committed to others in the vault. The safe address must have
+     * been set at least safe_address_delay seconds ago
+     * @param ctx PanicDrain context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn panic_drain(ctx: Context<PanicDrain>, index: u64) -> Result<()> {
+        msg!("Panic draining vault {}: {} to its safe address", index, ctx.accounts.vault.key());
+        let amount = ctx.accounts.drain()?;
+
+        emit!(PanicDrained {
+            user: ctx.accounts.user.key(),
+            vault: ctx.accounts.vault.key(),
+            safe_address: ctx.accounts.safe_address.key(),
+            amount,
+        });
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Closes the vault and transfers all remaining funds to user in a single step
+     * @dev Drains vault completely and closes the vault state account; active commitments
+     * block closing unless force_close is set, in which case an active stream is settled first.
+     * With beneficiaries configured the vault balance is split among them instead, and their
+     * accounts must be passed as remaining_accounts in the configured order. Only available
+     * once the owner has set the close delay to zero; otherwise use request_close and
+     * confirm_close. The settlement is written via set_return_data for CPI callers
+     * @param ctx Close context
+     * @param index Index of the vault among the user's vaults
+     * @param force_close Cancel an active stream and settle its vested portion before closing
+     * @return Result<CloseSettlement> Owner and final balance paid out, or error
+     */
+    pub fn close<'info>(
+        ctx: Context<'_, '_, '_, 'info, Close<'info>>,
+        index: u64,
+        force_close: bool,
+    ) -> Result<CloseSettlement> {
+        ctx.accounts.config.log_compute_units("close start");
+        let user = ctx.accounts.user.to_account_info();
+        let settlement = close_vault(ctx.accounts, ctx.remaining_accounts, index, force_close, false, user)?;
+        ctx.accounts.config.log_compute_units("close end");
+
+        Ok(settlement)
+    }
+
+    /**
+     * @notice Starts the close delay for a vault
+     * @dev Only callable by the vault owner; confirm_close succeeds once close_delay seconds
+     * have passed, and requesting again restarts the delay
+     * @param ctx CloseRequest context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn request_close(ctx: Context<CloseRequest>, index: u64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+
+        msg!("Requesting close of vault {} for vault state: {}", index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.close_requested_at = now;
+
+        emit!(CloseRequested {
+            user: ctx.accounts.user.key(),
+            vault_state: ctx.accounts.vault_state.key(),
+            close_after: now.saturating_add(ctx.accounts.vault_state.close_delay),
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Cancels a pending close request
+     * @param ctx CloseRequest context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success, or NoCloseRequested without a pending request
+     */
+    pub fn cancel_close(ctx: Context<CloseRequest>, index: u64) -> Result<()> {
+        require!(ctx.accounts.vault_state.close_requested_at != 0, VaultError::NoCloseRequested);
+
+        msg!("Cancelling close of vault {} for vault state: {}", index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.close_requested_at = 0;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Closes the vault once the close delay of a pending request has passed
+     * @dev Runs the same checks and payouts as close; see close for force_close and beneficiaries
+     * @param ctx Close context
+     * @param index Index of the vault among the user's vaults
+     * @param force_close Cancel an active stream and settle its vested portion before closing
+     * @return Result<CloseSettlement> Owner and final balance paid out, NoCloseRequested,
+     * CloseDelayActive or error
+     */
+    pub fn confirm_close<'info>(
+        ctx: Context<'_, '_, '_, 'info, Close<'info>>,
+        index: u64,
+        force_close: bool,
+    ) -> Result<CloseSettlement> {
+        let user = ctx.accounts.user.to_account_info();
+        close_vault(ctx.accounts, ctx.remaining_accounts, index, force_close, true, user)
+    }
+
+    /**
+     * @notice Closes the vault into accounts other than the owner's, e.g. when moving custody to a new wallet
+     * @dev Runs the same checks as close, with the drained balance sent to destination and the vault
+     * state rent to rent_destination. A pending close request is confirmed as by confirm_close;
+     * without one the close delay must be zero. Beneficiaries still take the balance when configured
+     * @param ctx CloseTo context
+     * @param index Index of the vault among the user's vaults
+     * @param force_close Cancel an active stream and settle its vested portion before closing
+     * @return Result<CloseSettlement> Owner and final balance paid out, or error
+     */
+    pub fn close_to<'info>(
+        ctx: Context<'_, '_, '_, 'info, CloseTo<'info>>,
+        index: u64,
+        force_close: bool,
+    ) -> Result<CloseSettlement> {
+        let confirmed = ctx.accounts.close.vault_state.close_requested_at != 0;
+        let destination = ctx.accounts.destination.to_account_info();
+        let settlement = close_vault(
+            &mut ctx.accounts.close,
+            ctx.remaining_accounts,
+            index,
+            force_close,
+            confirmed,
+            destination,
+        )?;
+        ctx.accounts.refund_state_rent()?;
+
+        Ok(settlement)
+    }
+
+    /**
+     * @notice Closes an expired vault on its owner's behalf
+     * @dev Callable by anyone once expires_at has passed; the whole balance and the vault state
+     * rent always go to the stored owner. Locks, renounced withdrawals, PAUSE_CLOSE and funds
+     * owed to streams or campaigns still block it, while the close delay and savings goal do not
+     * @param ctx CloseExpired context
+     * @param index Index of the vault among the owner's vaults
+     * @return Result<CloseSettlement> Owner and final balance paid out, or error
+     */
+    pub fn close_expired(ctx: Context<CloseExpired>, index: u64) -> Result<CloseSettlement> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        ctx.accounts.vault_state.require_expired(now)?;
+
+        msg!(
+            "Closing expired vault {}: {} for owner: {}",
+            index,
+            ctx.accounts.vault.key(),
+            ctx.accounts.owner.key()
+        );
+        let final_balance = ctx.accounts.close_expired(now)?;
+
+        if ctx.accounts.config.emit_events {
+            let clock = Clock::get()?;
+            emit!(VaultClosed {
+                event_version: EVENT_VERSION,
+                category: VaultClosed::CATEGORY,
+                user: ctx.accounts.owner.key(),
+                vault: ctx.accounts.vault.key(),
+                destination: ctx.accounts.owner.key(),
+                final_balance,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+            });
+        }
+
+        Ok(CloseSettlement {
+            owner: ctx.accounts.owner.key(),
+            final_balance,
+        })
+    }
+
+    /**
+     * @notice Removes a vault state whose vault PDA holds no lamports, refunding its rent to the owner
+     * @dev Cleans up states left without a funded vault, e.g. by program versions that did not seed
+     * the vault at initialize. Refuses any vault holding lamports, so it can't bypass the checks
+     * close applies to a funded vault
+     * @param ctx AbortInitialize context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn abort_initialize(ctx: Context<AbortInitialize>, index: u64) -> Result<()> {
+        msg!("Removing unfunded vault {}: {}", index, ctx.accounts.vault.key());
+        ctx.accounts.global_stats.close_vault(0);
+
+        if ctx.accounts.config.emit_events {
+            let clock = Clock::get()?;
+            emit!(VaultClosed {
+                event_version: EVENT_VERSION,
+                category: VaultClosed::CATEGORY,
+                user: ctx.accounts.user.key(),
+                vault: ctx.accounts.vault.key(),
+                destination: ctx.accounts.user.key(),
+                final_balance: 0,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+            });
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets when the vault expires and anyone can close it to the owner
+     * @dev Only callable by the vault owner; zero removes the expiry
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param expires_at Unix timestamp after which close_expired is allowed (0 for never)
+     * @return Result<()> Success or error
+     */
+    pub fn set_expiry(ctx: Context<UpdateVault>, index: u64, expires_at: i64) -> Result<()> {
+        let now = ctx.accounts.vault_state.checked_now()?;
+        require!(expires_at == 0 || expires_at > now, VaultError::InvalidExpiry);
+
+        msg!("Setting expiry to {} for vault state {}: {}", expires_at, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.expires_at = expires_at;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets how long a close request must wait before confirm_close succeeds
+     * @dev Only callable by the vault owner; vaults start at CLOSE_DELAY and zero re-enables
+     * the single-step close
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param delay Close delay in seconds
+     * @return Result<()> Success or error
+     */
+    pub fn set_close_delay(ctx: Context<UpdateVault>, index: u64, delay: i64) -> Result<()> {
+        require!(delay >= 0, VaultError::InvalidCloseDelay);
+
+        msg!("Setting close delay to {} seconds for vault state {}: {}", delay, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.close_delay = delay;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Opts the vault into closing itself once a withdraw leaves only rent behind
+     * @dev Only callable by the vault owner; the auto-close still honours the close delay,
+     * PAUSE_CLOSE and active streams or campaigns, and is skipped while any of them applies
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param enabled Whether withdraw closes an emptied vault
+     * @return Result<()> Success or error
+     */
+    pub fn set_auto_close(ctx: Context<UpdateVault>, index: u64, enabled: bool) -> Result<()> {
+        msg!("Setting auto-close to {} for vault state {}: {}", enabled, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.auto_close = enabled;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Opts the vault in or out of withdrawals by the config admin
+     * @dev Only callable by the vault owner; admin_withdraw fails on vaults that have not opted in
+     * @param ctx UpdateVault context
+     * @param index Index of the vault among the user's vaults
+     * @param enabled Whether the config admin may admin_withdraw from the vault
+     * @return Result<()> Success or error
+     */
+    pub fn set_admin_managed(ctx: Context<UpdateVault>, index: u64, enabled: bool) -> Result<()> {
+        msg!("Setting admin management to {} for vault state {}: {}", enabled, index, ctx.accounts.vault_state.key());
+        ctx.accounts.vault_state.admin_managed = enabled;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Turns the vault lifecycle events on or off for every vault
+     * @dev Only callable by the config admin; when off, VaultInitialized, FundsDeposited,
+     * FundsWithdrawn, VaultClosed and their compact forms are skipped to save compute
+     * @param ctx UpdateConfig context
+     * @param enabled Whether the events are emitted
+     * @return Result<()> Success or error
+     */
+    pub fn set_emit_events(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        msg!("Setting event emission to {}", enabled);
+        ctx.accounts.config.emit_events = enabled;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the shortest lock owners can set on their vaults
+     * @dev Only callable by the config admin; keeps a short-lived lock from passing for a locked
+     * vault. Applies to new unlock times only, existing locks are untouched
+     * @param ctx UpdateConfig context
+     * @param duration Minimum lock duration in seconds (0 only rejects unlock times in the past)
+     * @return Result<()> Success or error
+     */
+    pub fn set_min_lock_duration(ctx: Context<UpdateConfig>, duration: i64) -> Result<()> {
+        require!(duration >= 0, VaultError::InvalidLockDuration);
+
+        msg!("Setting minimum lock duration to {} seconds", duration);
+        ctx.accounts.config.min_lock_duration = duration;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Turns compute unit logging on or off for deposit, withdraw and close
+     * @dev Only callable by the config admin; a diagnostic for sizing client compute budgets,
+     * logging the remaining compute units at the start and end of each of those instructions
+     * @param ctx UpdateConfig context
+     * @param enabled Whether compute units are logged
+     * @return Result<()> Success or error
+     */
+    pub fn set_log_compute(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        msg!("Setting compute unit logging to {}", enabled);
+        ctx.accounts.config.log_compute = enabled;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Requires owner withdrawals to be top-level instructions
+     * @dev Only callable by the config admin; when enabled, a program invoking a withdrawal
+     * through CPI is rejected with CpiNotAllowed
+     * @param ctx UpdateConfig context
+     * @param enabled Whether the guard is on
+     * @return Result<()> Success or error
+     */
+    pub fn set_cpi_guard(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        msg!("Setting CPI guard to {}", enabled);
+        ctx.accounts.config.cpi_guard = enabled;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the reward rate vaults accrue from the reward treasury, creating the reward config on first use
+     * @dev Only callable by the config admin; the operator funds the reward treasury PDA with a plain
+     * transfer, and a zero rate stops new rewards
+     * @param ctx SetRewardRate context
+     * @param rate Reward per 10^12 lamports of vault balance per second
+     * @return Result<()> Success or error
+     */
+    pub fn set_reward_rate(ctx: Context<SetRewardRate>, rate: u64) -> Result<()> {
+        msg!("Setting reward rate to {} per 10^12 lamports per second", rate);
+        ctx.accounts.reward_config.set_inner(RewardConfig {
+            rate,
+            bump: ctx.bumps.reward_config,
+            treasury_bump: ctx.bumps.reward_treasury,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Pays the vault the reward earned on its balance since the last accrual
+     * @dev The reward is elapsed seconds * rate * balance above rent, scaled down by REWARD_RATE_SCALE
+     * and capped at what the reward treasury holds. The first accrual only starts the clock, so vaults
+     * are not rewarded for time before they opted in. Deposits and withdrawals restart the clock, so
+     * a balance is never rewarded for time before it reached the vault
+     * @param ctx Accrue context
+     * @param index Index of the vault among the user's vaults
+     * @return Result<()> Success or error
+     */
+    pub fn accrue(ctx: Context<Accrue>, index: u64) -> Result<()> {
+        let reward = ctx.accounts.accrue()?;
+        msg!("Accrued {} lamports of rewards to vault {}: {}", reward, index, ctx.accounts.vault.key());
+        ctx.accounts.vault_state.assert_invariants(&ctx.accounts.vault)?;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Starts or updates a deposit match campaign, creating the match config on first use
+     * @dev Only callable by the config admin; the operator funds the match treasury PDA with a plain
+     * transfer. The budget replaces what was left of the previous one, and a zero ratio stops matching
+     * @param ctx SetDepositMatch context
+     * @param ratio_bps Share of each deposit matched, out of BPS_DENOMINATOR
+     * @param budget Total lamports the campaign may match across all vaults
+     * @param per_vault_cap Maximum lamports matched into a single vault (0 for no cap)
+     * @return Result<()> Success or error
+     */
+    pub fn set_deposit_match(ctx: Context<SetDepositMatch>, ratio_bps: u16, budget: u64, per_vault_cap: u64) -> Result<()> {
+        require!(ratio_bps <= BPS_DENOMINATOR, VaultError::InvalidPercent);
+
+        msg!(
+            "Setting deposit match to {} bps with a budget of {} lamports and a per-vault cap of {} lamports",
+            ratio_bps,
+            budget,
+            per_vault_cap
+        );
+        ctx.accounts.match_config.set_inner(MatchConfig {
+            ratio_bps,
+            budget,
+            per_vault_cap,
+            bump: ctx.bumps.match_config,
+            treasury_bump: ctx.bumps.match_treasury,
+        });
+
+        Ok(())
+    }
+
+    /**
+     * @notice Deposits funds and adds a match from the match treasury
+     * @dev Same checks as deposit; the match is the ratio of the deposit, limited by the remaining
+     * budget, the vault's remaining cap and the treasury balance, and is zero once any runs out
+     * rather than failing the deposit. Matched lamports are not counted in total_deposited
+     * @param ctx MatchedPayment context
+     * @param index Index of the vault among the user's vaults
+     * @param amount Amount to deposit in lamports
+     * @return Result<()> Success or error
+     */
+    pub fn deposit_matched(ctx: Context<MatchedPayment>, index: u64, amount: u64) -> Result<()> {
+        let payment = &mut ctx.accounts.payment;
+        payment.vault_state.require_not_paused(VaultState::PAUSE_DEPOSIT)?;
+        payment.vault_state.require_direct_deposit(amount)?;
+
+        msg!("Depositing {} lamports to vault {}: {} with a match", amount, index, payment.vault.key());
+        payment.deposit(amount)?;
+        payment.report_threshold_crossing();
+
+        let points_earned = payment.accrue_points(amount)?;
+        payment.emit_deposited(amount, points_earned, 1, [0; MEMO_LEN])?;
+
+        let matched_amount = ctx.accounts.match_deposit(amount)?;
+        if matched_amount == 0 {
+            msg!("Deposit match exhausted, no match paid");
+        }
+
+        emit!(DepositMatched {
+            user: ctx.accounts.payment.user.key(),
+            vault: ctx.accounts.payment.vault.key(),
+            user_amount: amount,
+            matched_amount,
+        });
+        ctx.accounts.payment.assert_invariants()?;
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Account validation struct for vault initialization
+ * @dev Creates PDA accounts for vault state and vault seeded by the user and vault index; an
+ * existing vault state is loaded instead so a second initialize can fail with AlreadyInitialized
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = VaultState::DISCRIMINATOR.len() + VaultState::INIT_SPACE,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Only read for its emit_events flag
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Protocol-wide vault count and TVL, created by the first vault initialization
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = GlobalStats::DISCRIMINATOR.len() + GlobalStats::INIT_SPACE,
+        seeds = [GlobalStats::SEED],
+        bump,
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Initialize<'info> {
+    /**
+     * @notice Initializes vault state and funds vault with rent-exempt amount
+     * @dev Sets bump seeds and tops the vault up to the minimum balance for rent exemption,
+     * skipping the transfer when the vault PDA already holds it; a vault state closed by close
+     * is wiped, so initializing again after a close starts from scratch
+     * @param index Index of the vault among the user's vaults
+     * @param external_ref Opaque off-chain reference ID stored with the vault
+     * @param label Display name for the vault, all zeros when unlabeled
+     * @param bumps Bump seeds from account initialization
+     * @return Result<()> Success or AlreadyInitialized
+     */
+    fn initialize(&mut self, index: u64, external_ref: u64, label: [u8; LABEL_LEN], bumps: &InitializeBumps) -> Result<()> {
+        require!(!self.vault_state.is_initialized, VaultError::AlreadyInitialized);
+
+        self.vault_state.set_inner(VaultState::new(
+            self.user.key(),
+            index,
+            external_ref,
+            label,
+            bumps.vault_state,
+            bumps.vault,
+        )?);
+
+        seed_vault(&self.system_program, &self.vault, self.user.to_account_info())?;
+
+        self.global_stats.bump = bumps.global_stats;
+        self.global_stats.open_vault(self.vault.get_lamports())
+    }
+}
+
+/**
+ * @notice Account validation struct for initializing a vault on behalf of another wallet
+ * @dev Like Initialize, but the PDAs are seeded by the beneficiary, who becomes the owner,
+ * while the payer signs and pays the rent
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct InitializeFor<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub beneficiary: SystemAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = VaultState::DISCRIMINATOR.len() + VaultState::INIT_SPACE,
+        seeds = [VaultState::STATE_SEED, beneficiary.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, beneficiary.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Only read for its emit_events flag
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Protocol-wide vault count and TVL, created by the first vault initialization
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = GlobalStats::DISCRIMINATOR.len() + GlobalStats::INIT_SPACE,
+        seeds = [GlobalStats::SEED],
+        bump,
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeFor<'info> {
+    /**
+     * @notice Initializes the beneficiary's vault state and funds the vault from the payer
+     * @param index Index of the vault among the beneficiary's vaults
+     * @param external_ref Opaque off-chain reference ID stored with the vault
+     * @param label Display name for the vault, all zeros when unlabeled
+     * @param bumps Bump seeds from account initialization
+     * @return Result<()> Success or AlreadyInitialized
+     */
+    fn initialize(&mut self, index: u64, external_ref: u64, label: [u8; LABEL_LEN], bumps: &InitializeForBumps) -> Result<()> {
+        require!(!self.vault_state.is_initialized, VaultError::AlreadyInitialized);
+
+        self.vault_state.set_inner(VaultState::new(
+            self.beneficiary.key(),
+            index,
+            external_ref,
+            label,
+            bumps.vault_state,
+            bumps.vault,
+        )?);
+
+        seed_vault(&self.system_program, &self.vault, self.payer.to_account_info())?;
+
+        self.global_stats.bump = bumps.global_stats;
+        self.global_stats.open_vault(self.vault.get_lamports())
+    }
+}
+
+/**
+ * @notice Account validation struct for withdrawals to a third party
+ * @dev Wraps the regular withdrawal accounts; the vault PDA is still derived from the user
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct WithdrawTo<'info> {
+    pub payment: Payment<'info>,
+
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+}
+
+/**
+ * @notice Account validation struct for adding a whitelisted withdrawal recipient
+ * @dev The owner pays for the whitelist on first use
+ */
+#[derive(Accounts)]
+pub struct AddRecipient<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Whitelist::DISCRIMINATOR.len() + Whitelist::INIT_SPACE,
+        seeds = [Whitelist::SEED, user.key().as_ref()],
+        bump,
+    )]
+    pub whitelist: Account<'info, Whitelist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for removing a whitelisted withdrawal recipient
+ */
+#[derive(Accounts)]
+pub struct RemoveRecipient<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [Whitelist::SEED, user.key().as_ref()],
+        bump = whitelist.bump
+    )]
+    pub whitelist: Account<'info, Whitelist>,
+}
+
+/**
+ * @notice Account validation struct for withdrawals to a whitelisted recipient
+ * @dev Wraps the regular withdrawal accounts; the whitelist is derived from the user
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct WithdrawToWhitelisted<'info> {
+    pub payment: Payment<'info>,
+
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+
+    #[account(
+        seeds = [Whitelist::SEED, payment.user.key().as_ref()],
+        bump = whitelist.bump
+    )]
+    pub whitelist: Account<'info, Whitelist>,
+}
+
+/**
+ * @notice Account validation struct for paying a recipient while saving a share
+ * @dev Wraps the regular deposit accounts; the payee is pinned to the recipient argument
+ */
+#[derive(Accounts)]
+#[instruction(index: u64, payment_amount: u64, save_bps: u16, recipient: Pubkey)]
+pub struct PayAndSave<'info> {
+    pub payment: Payment<'info>,
+
+    #[account(mut, address = recipient)]
+    pub payee: SystemAccount<'info>,
+}
+
+/**
+ * @notice Account validation struct for campaign-attributed deposits
+ * @dev Wraps the regular deposit accounts and lazily creates the campaign's stats account
+ */
+#[derive(Accounts)]
+#[instruction(index: u64, amount: u64, campaign_id: u32)]
+pub struct CampaignPayment<'info> {
+    pub payment: Payment<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payment.user,
+        space = CampaignStats::DISCRIMINATOR.len() + CampaignStats::INIT_SPACE,
+        seeds = [CampaignStats::SEED, campaign_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub campaign_stats: Account<'info, CampaignStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for creating the program config
+ * @dev The signer must be the upgrade authority recorded in the program's ProgramData
+ */
+#[derive(Accounts)]
+pub struct InitConfig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = Config::DISCRIMINATOR.len() + Config::INIT_SPACE,
+        seeds = [Config::SEED],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::AnchorVault>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(payer.key()) @ VaultError::NotUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for admin updates to the program config
+ * @dev Validates the signer against the config admin
+ */
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = admin @ VaultError::NotConfigAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/**
+ * @notice Account validation struct for toggling the program pause
+ * @dev Validates the signer against the config admin
+ */
+#[derive(Accounts)]
+pub struct SetPause<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = admin @ VaultError::NotConfigAdmin
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/**
+ * @notice Account validation struct for deposit and withdrawal operations
+ * @dev Validates vault ownership and account relationships; the vault is selected by the
+ * index instruction argument
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct Payment<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::NotVaultAuthority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = treasury @ VaultError::InvalidTreasury,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Receives the withdrawal fee, if any
+    #[account(mut)]
+    pub treasury: SystemAccount<'info>,
+
+    /// CHECK: Pinned to the instructions sysvar, only read when the config's CPI guard is on
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Payment<'info> {
+    /**
+     * @notice Checks the vault state against the vault's current balance
+     * @return Result<()> Success or InvariantViolation
+     */
+    fn assert_invariants(&self) -> Result<()> {
+        self.vault_state.assert_invariants(&self.vault)
+    }
+
+    /**
+     * @notice Fails when the CPI guard is on and this instruction was invoked by another program
+     * @return Result<()> Success or CpiNotAllowed
+     */
+    fn require_top_level(&self) -> Result<()> {
+        self.config.require_top_level(&self.instructions.to_account_info())
+    }
+
+    /**
+     * @notice Deposits funds from user to vault
+     * @dev Transfers lamports using system program CPI after checking the vault's minimum deposit
+     * @param amount Amount to deposit in lamports
+     * @return Result<()> Success or error
+     */
+    fn deposit(&mut self, amount: u64) -> Result<()> {
+        let user = self.user.to_account_info();
+        self.deposit_from(&[user], &[amount])
+    }
+
+    /**
+     * @notice Moves lamports from the user to the vault and earmarks them as rent buffer
+     * @param extra Amount added to the buffer in lamports
+     * @return Result<()> Success or error
+     */
+    fn fund_rent_buffer(&mut self, extra: u64) -> Result<()> {
+        self.vault_state.rent_buffer = self.vault_state.rent_buffer.checked_add(extra).ok_or(VaultError::MathOverflow)?;
+
+        let transfer_accounts = Transfer {
+            from: self.user.to_account_info(),
+            to: self.vault.to_account_info(),
+        };
+        transfer(CpiContext::new(self.system_program.to_account_info(), transfer_accounts), extra)?;
+
+        let now = self.vault_state.now()?;
+        self.vault_state.record_flow(now, extra, 0);
+        self.global_stats.record_inflow(extra)
+    }
+
+    /**
+     * @notice Deposits funds from one or more wallets to the vault
+     * @dev The minimum deposit and maximum balance apply to the total, which is added to
+     * total_deposited before any lamports move; each source pays its own amount through a
+     * separate system program CPI
+     * @param sources Signing wallets funding the deposit
+     * @param amounts Amount in lamports paid by the source at the same position
+     * @return Result<()> Success or error
+     */
+    fn deposit_from(&mut self, sources: &[AccountInfo<'info>], amounts: &[u64]) -> Result<()> {
+        let amount = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(VaultError::MathOverflow)?;
+        self.vault_state.require_min_deposit(amount)?;
+        self.vault_state.record_deposit(amount)?;
+
+        let max_balance = self.vault_state.max_balance;
+        if max_balance > 0 {
+            let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+            let usable_balance = self.vault.get_lamports().saturating_sub(rent_exempt);
+            require!(
+                usable_balance.saturating_add(amount) <= max_balance,
+                VaultError::ExceedsMaxBalance
+            );
+        }
+
+        for (source, source_amount) in sources.iter().zip(amounts) {
+            let transfer_accounts = Transfer {
+                from: source.clone(),
+                to: self.vault.to_account_info(),
+            };
+
+            let transfer_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+
+            transfer(transfer_ctx, *source_amount)?;
+        }
+        self.global_stats.record_inflow(amount)?;
+
+        let now = self.vault_state.now()?;
+        self.vault_state.record_flow(now, amount, 0);
+        self.vault_state.last_deposit_time = now;
+        self.vault_state.last_activity = now;
+
+        let goal = self.vault_state.goal_amount;
+        if goal > 0 && !self.vault_state.goal_reached {
+            let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+            let usable_balance = self.vault.get_lamports().saturating_sub(rent_exempt);
+
+            if usable_balance >= goal {
+                self.vault_state.goal_reached = true;
+
+                emit!(GoalReached {
+                    user: self.user.key(),
+                    vault: self.vault.key(),
+                    goal,
+                    balance: usable_balance,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Runs every check a withdrawal must pass and feeds it to the activity monitor
+     * @param amount Amount to withdraw in lamports
+     * @param approvals Accounts offered as withdrawal approvers
+     * @return Result<()> Success or error
+     */
+    fn check_withdrawal(&mut self, amount: u64, approvals: &[AccountInfo]) -> Result<()> {
+        self.require_top_level()?;
+        self.vault_state.check_withdrawal(&self.vault, amount, Some(approvals))
+    }
+
+    /**
+     * @notice Computes a share of the balance above rent, committed funds, the balance floor,
+     * locked savings and the partially locked amount, capped at the unwithdrawn vested amount during a vesting window
+     * and at what the lifetime withdrawal cap has left
+     * @param basis_points Share in basis points, at most BPS_DENOMINATOR
+     * @return Result<u64> Amount in lamports, rounded down, or error
+     */
+    fn withdrawable_share(&self, basis_points: u16) -> Result<u64> {
+        let now = self.vault_state.checked_now()?;
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        let retained = rent_exempt
+            .saturating_add(self.vault_state.committed_balance(now))
+            .saturating_add(self.vault_state.balance_floor())
+            .saturating_add(self.vault_state.locked_savings(now))
+            .saturating_add(self.vault_state.partially_locked(now));
+        let withdrawable = self
+            .vault
+            .get_lamports()
+            .saturating_sub(retained)
+            .min(self.vault_state.vesting_available(now))
+            .min(self.vault_state.lifetime_available());
+
+        Ok((u128::from(withdrawable) * u128::from(basis_points) / u128::from(BPS_DENOMINATOR)) as u64)
+    }
+
+    /**
+     * @notice Withdraws funds from vault to a destination account, less the program's withdrawal fee
+     * @dev Uses PDA signing to authorize both transfers from vault; the fee goes to the treasury
+     * @param amount Amount to withdraw in lamports, including the fee
+     * @param to Account receiving the funds, normally the user
+     * @return Result<u64> Fee paid to the treasury in lamports, or error
+     */
+    fn withdraw(&mut self, amount: u64, to: AccountInfo<'info>) -> Result<u64> {
+        let (net_amount, fee) = self.config.withdrawal_split(amount)?;
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            to,
+            &self.user.key(),
+            &self.vault_state,
+            net_amount,
+        )?;
+
+        if fee > 0 {
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                self.treasury.to_account_info(),
+                &self.user.key(),
+                &self.vault_state,
+                fee,
+            )?;
+        }
+        self.global_stats.record_outflow(amount);
+
+        // Verify vault maintains rent exemption after withdrawal
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        require_gte!(self.vault.get_lamports(), rent_exempt);
+
+        let now = self.vault_state.now()?;
+        self.vault_state.record_withdrawal(now, amount)?;
+
+        Ok(fee)
+    }
+
+    /**
+     * @notice Reports the first time the lifetime deposit total passes the reporting threshold
+     * @dev Emits ReportingThresholdCrossed at most once per configured threshold; never blocks
+     */
+    fn report_threshold_crossing(&mut self) {
+        let vault_state = &mut self.vault_state;
+
+        if vault_state.reporting_threshold > 0
+            && !vault_state.threshold_reported
+            && vault_state.total_deposited > vault_state.reporting_threshold
+        {
+            vault_state.threshold_reported = true;
+
+            emit!(ReportingThresholdCrossed {
+                user: self.user.key(),
+                total: vault_state.total_deposited,
+            });
+        }
+    }
+
+    /**
+     * @notice Credits reward points for a deposit at the vault's configured rate
+     * @param amount Amount deposited in lamports
+     * @return Result<u64> Points earned by this deposit or MathOverflow
+     */
+    fn accrue_points(&mut self, amount: u64) -> Result<u64> {
+        let lamports_per_point = self.vault_state.lamports_per_point;
+        if lamports_per_point == 0 {
+            return Ok(0);
+        }
+
+        let points_earned = amount / lamports_per_point;
+        self.vault_state.reward_points = self
+            .vault_state
+            .reward_points
+            .checked_add(points_earned)
+            .ok_or(VaultError::MathOverflow)?;
+
+        Ok(points_earned)
+    }
+
+    /**
+     * @notice Emits the deposit event, compact when the vault opted into compact events
+     * @dev A deposit carrying a memo always gets the full event, as the compact one has no room for it;
+     * nothing is emitted while the config turns events off
+     * @param amount Amount deposited in lamports
+     * @param points_earned Reward points credited for the deposit
+     * @param source_count Number of wallets the deposit was funded from
+     * @param memo Payment reference of the deposit (all zeros for none)
+     * @return Result<()> Success or error
+     */
+    fn emit_deposited(&self, amount: u64, points_earned: u64, source_count: u8, memo: [u8; MEMO_LEN]) -> Result<()> {
+        if !self.config.emit_events {
+            return Ok(());
+        }
+
+        if self.vault_state.compact_events && memo == [0; MEMO_LEN] {
+            emit!(CompactEvent { kind: CompactEvent::KIND_DEPOSIT, value: amount });
+        } else {
+            let clock = Clock::get()?;
+            emit!(FundsDeposited {
+                event_version: EVENT_VERSION,
+                category: FundsDeposited::CATEGORY,
+                user: self.user.key(),
+                vault: self.vault.key(),
+                amount,
+                points_earned,
+                total_deposited: self.vault_state.total_deposited,
+                source_count,
+                memo,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+            });
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Emits the withdrawal event, compact when the vault opted into compact events
+     * @dev Nothing is emitted while the config turns events off
+     * @param amount Amount withdrawn in lamports, including the fee
+     * @param fee Withdrawal fee paid to the treasury in lamports
+     * @param recipient Account the funds were sent to
+     * @return Result<()> Success or error
+     */
+    fn emit_withdrawn(&self, amount: u64, fee: u64, recipient: Pubkey) -> Result<()> {
+        if !self.config.emit_events {
+            return Ok(());
+        }
+
+        if self.vault_state.compact_events {
+            emit!(CompactEvent { kind: CompactEvent::KIND_WITHDRAW, value: amount });
+        } else {
+            let clock = Clock::get()?;
+            emit!(FundsWithdrawn {
+                event_version: EVENT_VERSION,
+                category: FundsWithdrawn::CATEGORY,
+                user: self.user.key(),
+                vault: self.vault.key(),
+                recipient,
+                amount,
+                fee,
+                total_withdrawn: self.vault_state.total_withdrawn,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+                initiated_by_admin: false,
+            });
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Closes an auto-close vault that a withdrawal left holding only rent
+     * @dev Sends the vault's rent and the state account's rent to the user. Skipped, without
+     * failing the withdrawal, whenever close or confirm_close would refuse the vault, and for
+     * token vaults, whose token account withdraw doesn't see
+     * @param index Index of the vault among the user's vaults
+     * @return Result<bool> Whether the vault was closed, or error
+     */
+    fn auto_close(&mut self, index: u64) -> Result<bool> {
+        if !self.vault_state.auto_close {
+            return Ok(false);
+        }
+
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        let vault_balance = self.vault.get_lamports();
+        if vault_balance > rent_exempt {
+            return Ok(false);
+        }
+
+        let now = self.vault_state.checked_now()?;
+        let vault_state = &self.vault_state;
+        if vault_state.require_not_paused(VaultState::PAUSE_CLOSE).is_err()
+            || vault_state.has_active_stream()
+            || vault_state.funding_raised > 0
+            || vault_state.mint != Pubkey::default()
+            || vault_state.require_close_allowed(now, vault_state.close_requested_at != 0).is_err()
+        {
+            msg!("Auto-close skipped for vault {}: {}", index, self.vault.key());
+            return Ok(false);
+        }
+
+        msg!("Auto-closing vault {}: {} with balance: {}", index, self.vault.key(), vault_balance);
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.user.to_account_info(),
+            &self.user.key(),
+            &self.vault_state,
+            vault_balance,
+        )?;
+        require!(self.vault.get_lamports() == 0, VaultError::CloseIncomplete);
+        self.vault_state.close(self.user.to_account_info())?;
+        self.global_stats.close_vault(vault_balance);
+
+        if self.config.emit_events {
+            let clock = Clock::get()?;
+            emit!(VaultClosed {
+                event_version: EVENT_VERSION,
+                category: VaultClosed::CATEGORY,
+                user: self.user.key(),
+                vault: self.vault.key(),
+                destination: self.user.key(),
+                final_balance: vault_balance,
+                timestamp: clock.unix_timestamp,
+                slot: clock.slot,
+            });
+        }
+
+        Ok(true)
+    }
+}
+
+/**
+ * @notice Account validation struct for withdrawals made by the vault's delegate
+ * @dev The delegate signs; the vault PDAs are derived from the owner, who receives the funds
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct WithdrawAsDelegate<'info> {
+    pub delegate: Signer<'info>,
+
+    #[account(mut, address = vault_state.authority)]
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.delegate != Pubkey::default() @ VaultError::NotVaultDelegate,
+        constraint = vault_state.delegate == delegate.key() @ VaultError::NotVaultDelegate
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = treasury @ VaultError::InvalidTreasury,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Receives the withdrawal fee, if any
+    #[account(mut)]
+    pub treasury: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawAsDelegate<'info> {
+    /**
+     * @notice Transfers funds from the vault to its owner, less the withdrawal fee
+     * @dev Uses PDA signing with the owner's vault seeds
+     * @param amount Amount to withdraw in lamports, including the fee
+     * @return Result<u64> Fee paid to the treasury in lamports, or error
+     */
+    fn withdraw(&mut self, amount: u64) -> Result<u64> {
+        let (net_amount, fee) = self.config.withdrawal_split(amount)?;
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.owner.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            net_amount,
+        )?;
+
+        if fee > 0 {
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                self.treasury.to_account_info(),
+                &self.owner.key(),
+                &self.vault_state,
+                fee,
+            )?;
+        }
+        self.global_stats.record_outflow(amount);
+
+        let now = self.vault_state.now()?;
+        self.vault_state.record_withdrawal(now, amount)?;
+
+        Ok(fee)
+    }
+}
+
+/**
+ * @notice Account validation struct for executing a scheduled withdrawal
+ * @dev Anyone can sign; the vault PDAs are derived from the owner and the recipient is pinned
+ * to the scheduled one
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct ExecuteScheduled<'info> {
+    pub executor: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == owner.key() @ VaultError::NotVaultAuthority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut, address = vault_state.scheduled_withdrawal.recipient)]
+    pub recipient: SystemAccount<'info>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = treasury @ VaultError::InvalidTreasury,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Receives the withdrawal fee, if any
+    #[account(mut)]
+    pub treasury: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ExecuteScheduled<'info> {
+    /**
+     * @notice Transfers the scheduled amount to the recipient, less the withdrawal fee, and clears the schedule
+     * @dev Uses PDA signing with the owner's vault seeds
+     * @param amount Amount to withdraw in lamports, including the fee
+     * @param now Current unix timestamp
+     * @return Result<u64> Fee paid to the treasury in lamports, or error
+     */
+    fn execute(&mut self, amount: u64, now: i64) -> Result<u64> {
+        let (net_amount, fee) = self.config.withdrawal_split(amount)?;
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.recipient.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            net_amount,
+        )?;
+
+        if fee > 0 {
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                self.treasury.to_account_info(),
+                &self.owner.key(),
+                &self.vault_state,
+                fee,
+            )?;
+        }
+        self.global_stats.record_outflow(amount);
+
+        self.vault_state.scheduled_withdrawal = ScheduledWithdrawal::default();
+        self.vault_state.record_withdrawal(now, amount)?;
+
+        Ok(fee)
+    }
+}
+
+/**
+ * @notice Account validation struct for admin withdrawals
+ * @dev The config admin signs; the vault PDAs are derived from the owner, who receives the funds
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct AdminWithdraw<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == owner.key() @ VaultError::NotVaultAuthority,
+        constraint = vault_state.admin_managed @ VaultError::NotAdminManaged
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = admin @ VaultError::NotConfigAdmin,
+        has_one = treasury @ VaultError::InvalidTreasury,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Receives the withdrawal fee, if any
+    #[account(mut)]
+    pub treasury: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AdminWithdraw<'info> {
+    /**
+     * @notice Transfers the amount from the vault to its owner, less the withdrawal fee
+     * @dev Uses PDA signing with the owner's vault seeds
+     * @param amount Amount to withdraw in lamports, including the fee
+     * @param now Current unix timestamp
+     * @return Result<u64> Fee paid to the treasury in lamports, or error
+     */
+    fn withdraw(&mut self, amount: u64, now: i64) -> Result<u64> {
+        let (net_amount, fee) = self.config.withdrawal_split(amount)?;
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.owner.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            net_amount,
+        )?;
+
+        if fee > 0 {
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                self.treasury.to_account_info(),
+                &self.owner.key(),
+                &self.vault_state,
+                fee,
+            )?;
+        }
+        self.global_stats.record_outflow(amount);
+
+        self.vault_state.record_withdrawal(now, amount)?;
+
+        Ok(fee)
+    }
+}
+
+/**
+ * @notice Account validation struct for owner-only vault configuration
+ * @dev Validates vault ownership and allows updating vault state settings; the vault is selected
+ * by the index instruction argument
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct UpdateVault<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+/**
+ * @notice Account validation struct for locking the vault
+ * @dev Same as UpdateVault, plus the config for its minimum lock duration
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct SetLock<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    /// Only read for its min_lock_duration
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/**
+ * @notice Account validation struct for requesting or cancelling a delayed close
+ * @dev The vault is selected by the index instruction argument, as in Close
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct CloseRequest<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::NotVaultAuthority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+/**
+ * @notice Account validation struct for read-only vault queries
+ * @dev Mirrors Payment without mutability, including the index selecting the vault; the owner
+ * does not need to sign
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct ViewVault<'info> {
+    pub user: SystemAccount<'info>,
+
+    #[account(
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+}
+
+impl<'info> ViewVault<'info> {
+    /**
+     * @notice Computes the balance a withdrawal can take without breaching what the vault retains
+     * @dev Retains rent, committed funds, the balance floor, locked savings and the partially
+     * locked amount, and caps the
+     * result at the unwithdrawn vested amount, as VaultState::check_withdrawal does
+     * @param now Current unix timestamp
+     * @return Result<u64> Withdrawable amount in lamports or error
+     */
+    fn withdrawable(&self, now: i64) -> Result<u64> {
+        let vault_state = &self.vault_state;
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        let retained = rent_exempt
+            .saturating_add(vault_state.committed_balance(now))
+            .saturating_add(vault_state.balance_floor())
+            .saturating_add(vault_state.locked_savings(now))
+            .saturating_add(vault_state.partially_locked(now));
+
+        Ok(self
+            .vault
+            .get_lamports()
+            .saturating_sub(retained)
+            .min(vault_state.vesting_available(now))
+            .min(vault_state.lifetime_available()))
+    }
+
+    /**
+     * @notice Collects the vault's addresses, bump seeds and balance
+     * @return VaultInfo Derivation details of the vault
+     */
+    fn info(&self) -> VaultInfo {
+        VaultInfo {
+            vault_state: self.vault_state.key(),
+            vault: self.vault.key(),
+            index: self.vault_state.index,
+            state_bump: self.vault_state.state_bump,
+            vault_bump: self.vault_state.vault_bump,
+            balance: self.vault.get_lamports(),
+        }
+    }
+
+    /**
+     * @notice Serializes the vault state and slices out at most MAX_STATE_CHUNK bytes
+     * @param offset Byte offset of the first byte to return
+     * @return Result<StateChunk> Requested bytes or InvalidStateOffset
+     */
+    fn state_chunk(&self, offset: u32) -> Result<StateChunk> {
+        let mut data = Vec::new();
+        self.vault_state.try_serialize(&mut data)?;
+
+        let start = offset as usize;
+        require!(start < data.len(), VaultError::InvalidStateOffset);
+        let end = data.len().min(start + MAX_STATE_CHUNK);
+
+        Ok(StateChunk {
+            total_len: data.len() as u32,
+            offset,
+            data: data[start..end].to_vec(),
+        })
+    }
+
+    /**
+     * @notice Collects the constraints enforced by deposit and withdraw
+     * @return Result<VaultConstraints> Active constraints or error
+     */
+    fn constraints(&self) -> Result<VaultConstraints> {
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        let now = self.vault_state.now()?;
+
+        Ok(VaultConstraints {
+            layout_version: VaultConstraints::LAYOUT_VERSION,
+            min_deposit: self.vault_state.min_deposit,
+            max_withdrawal: self.vault_state.max_withdrawal,
+            min_withdrawal: self.vault_state.min_withdrawal,
+            min_balance: rent_exempt,
+            approval_threshold: self.vault_state.approval_threshold,
+            required_approvals: self.vault_state.required_approvals,
+            paused_instructions: self.vault_state.paused_instructions,
+            unlock_time: self.vault_state.unlock_time,
+            daily_limit: self.vault_state.current_daily_limit(now),
+            max_balance: self.vault_state.max_balance,
+            balance_floor: self.vault_state.balance_floor(),
+            max_withdrawals_per_window: self.vault_state.max_withdrawals_per_window,
+            lifetime_withdraw_cap: self.vault_state.lifetime_withdraw_cap,
+        })
+    }
+
+    /**
+     * @notice Scores the vault's health out of 100
+     * @dev Sum of four factors. Solvency (40): the balance covers rent plus committed funds.
+     * Funding (20): the free balance exceeds the rent-exempt minimum, 10 if merely non-zero.
+     * Activity (20): the last deposit is within 30 days, 10 if within 90 days.
+     * Protections (20): 5 each for a daily limit, a minimum withdrawal, approvals and an active lock
+     * @return Result<u8> Health score or error
+     */
+    fn health_score(&self) -> Result<u8> {
+        const RECENT_DAYS: i64 = 30;
+        const STALE_DAYS: i64 = 90;
+
+        let vault_state = &self.vault_state;
+        let now = vault_state.now()?;
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        let retained = rent_exempt.saturating_add(vault_state.committed_balance(now));
+        let balance = self.vault.get_lamports();
+        let free_balance = balance.saturating_sub(retained);
+
+        let solvency = if balance >= retained { 40 } else { 0 };
+
+        let funding = if free_balance > rent_exempt {
+            20
+        } else if free_balance > 0 {
+            10
+        } else {
+            0
+        };
+
+        let idle = now.saturating_sub(vault_state.last_deposit_time);
+        let activity = if vault_state.last_deposit_time == 0 {
+            0
+        } else if idle <= RECENT_DAYS * SECONDS_PER_DAY {
+            20
+        } else if idle <= STALE_DAYS * SECONDS_PER_DAY {
+            10
+        } else {
+            0
+        };
+
+        let protections = [
+            vault_state.current_daily_limit(now) > 0,
+            vault_state.min_withdrawal > 0,
+            vault_state.required_approvals > 0,
+            vault_state.is_locked(now),
+        ]
+        .iter()
+        .filter(|configured| **configured)
+        .count() as u8
+            * 5;
+
+        Ok(solvency + funding + activity + protections)
+    }
+
+    /**
+     * @notice Evaluates every withdraw check against a prospective amount
+     * @dev Keep in sync with the require! checks in VaultState::check_withdrawal
+     * @param amount Amount the client intends to withdraw in lamports
+     * @return Result<RateLimitStatus> Status of each limit or error
+     */
+    fn rate_limit_status(&self, amount: u64) -> Result<RateLimitStatus> {
+        let vault_state = &self.vault_state;
+        let now = vault_state.now()?;
+        let available = self.withdrawable(now)?;
+
+        let valid_amount = amount > 0;
+        let within_per_tx_limit = amount <= vault_state.max_withdrawal;
+        let meets_minimum = vault_state.min_withdrawal == 0 || amount >= vault_state.min_withdrawal;
+        let within_available = amount <= available;
+        let requires_approvals = vault_state.required_approvals > 0 && amount > vault_state.approval_threshold;
+        let paused = vault_state.paused_instructions & VaultState::PAUSE_WITHDRAW != 0;
+        let recent_deposit = vault_state.has_recent_deposit(now);
+        let locked = vault_state.is_locked(now);
+        let daily_remaining = vault_state.daily_remaining(now);
+        let within_daily_limit = amount <= daily_remaining;
+        let within_withdrawal_window = vault_state.within_withdrawal_window(now);
+        let renounced = vault_state.withdrawals_renounced;
+
+        Ok(RateLimitStatus {
+            allowed: !paused
+                && !locked
+                && recent_deposit
+                && valid_amount
+                && within_per_tx_limit
+                && meets_minimum
+                && within_available
+                && within_daily_limit
+                && within_withdrawal_window
+                && !renounced,
+            valid_amount,
+            within_per_tx_limit,
+            per_tx_limit: vault_state.max_withdrawal,
+            meets_minimum,
+            min_withdrawal: vault_state.min_withdrawal,
+            within_available,
+            available,
+            requires_approvals,
+            paused,
+            recent_deposit,
+            locked,
+            within_daily_limit,
+            daily_remaining,
+            within_withdrawal_window,
+            renounced,
+        })
+    }
+}
+
+/**
+ * @notice Account validation struct for authorization queries
+ * @dev Read-only; the grant is only needed to answer for a spending grantee
+ */
+#[derive(Accounts)]
+#[instruction(index: u64, key: Pubkey)]
+pub struct AuthorizationQuery<'info> {
+    pub user: SystemAccount<'info>,
+
+    #[account(
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [SpendingGrant::SEED, vault_state.key().as_ref(), key.as_ref()],
+        bump = grant.bump
+    )]
+    pub grant: Option<Account<'info, SpendingGrant>>,
+}
+
+impl<'info> AuthorizationQuery<'info> {
+    /**
+     * @notice Evaluates the gating of the handlers behind action for key
+     * @dev Keep in sync with the signer constraints and require_not_paused calls of each handler
+     * @param key Account whose authorization is queried
+     * @param action One of the VaultState::ACTION_* codes
+     * @return Result<bool> Whether the action is authorized, or InvalidAction
+     */
+    fn is_authorized(&self, key: Pubkey, action: u8) -> Result<bool> {
+        let vault_state = &self.vault_state;
+        let now = vault_state.now()?;
+        let is_owner = key == self.user.key();
+        let unpaused = |flag: u8| vault_state.paused_instructions & flag == 0;
+
+        let authorized = match action {
+            VaultState::ACTION_DEPOSIT => is_owner && unpaused(VaultState::PAUSE_DEPOSIT),
+            VaultState::ACTION_WITHDRAW => {
+                let granted = self
+                    .grant
+                    .as_ref()
+                    .is_some_and(|grant| now < grant.expires_at && grant.spent < grant.ceiling);
+                let delegated = key != Pubkey::default() && key == vault_state.delegate;
+                (is_owner || granted || delegated)
+                    && unpaused(VaultState::PAUSE_WITHDRAW)
+                    && !vault_state.is_locked(now)
+                    && !vault_state.withdrawals_renounced
+            }
+            VaultState::ACTION_CLOSE => {
+                is_owner
+                    && unpaused(VaultState::PAUSE_CLOSE)
+                    && !vault_state.is_locked(now)
+                    && !vault_state.withdrawals_renounced
+            }
+            VaultState::ACTION_CLAIM_STREAM => {
+                vault_state.has_active_stream()
+                    && key == vault_state.stream_recipient
+                    && unpaused(VaultState::PAUSE_STREAM)
+            }
+            VaultState::ACTION_CONTRIBUTE => {
+                vault_state.funding_goal > 0
+                    && now < vault_state.funding_deadline
+                    && unpaused(VaultState::PAUSE_CONTRIBUTE)
+            }
+            VaultState::ACTION_APPROVE => {
+                vault_state.required_approvals > 0
+                    && key != Pubkey::default()
+                    && vault_state.approvers.contains(&key)
+            }
+            VaultState::ACTION_CONFIGURE => is_owner,
+            _ => return err!(VaultError::InvalidAction),
+        };
+
+        Ok(authorized)
+    }
+}
+
+/**
+ * @notice Account validation struct for pure helpers that read no accounts
+ */
+#[derive(Accounts)]
+pub struct Stateless {}
+
+/**
+ * @notice Account validation struct for vault closure
+ * @dev Closes vault state account and transfers remaining funds; the vault is selected by the
+ * index instruction argument
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct Close<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::NotVaultAuthority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Recipient of an active stream, required only when force closing over one
+    #[account(mut, address = vault_state.stream_recipient)]
+    pub stream_recipient: Option<SystemAccount<'info>>,
+
+    /// The vault's token account, required only when closing a token vault
+    #[account(
+        associated_token::mint = vault_state.mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Close<'info> {
+    /**
+     * @notice Pays out the vested portion of the active stream ahead of a forced close
+     * @dev The unvested remainder stays in the vault and is drained to the owner by close
+     * @param now Current unix timestamp
+     * @return Result<(u64, u64)> Vested amount paid and unvested amount returned, or error
+     */
+    fn settle_stream(&mut self, now: i64) -> Result<(u64, u64)> {
+        let recipient = self
+            .stream_recipient
+            .as_ref()
+            .ok_or(VaultError::StreamRecipientRequired)?;
+
+        let vested = self.vault_state.stream_claimable(now);
+        let returned = self.vault_state.stream_total.saturating_sub(self.vault_state.stream_vested(now));
+
+        if vested > 0 {
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                recipient.to_account_info(),
+                &self.user.key(),
+                &self.vault_state,
+                vested,
+            )?;
+        }
+
+        Ok((vested, returned))
+    }
+
+    /**
+     * @notice Closes vault and transfers all remaining funds to the destination
+     * @dev Drains vault completely using PDA signing; the vault state rent is left to the
+     * close constraint, which only runs on exit. An already empty vault skips the transfer
+     * @param to Account receiving the funds, normally the user
+     * @return Result<()> Success or error
+     */
+    fn close(&mut self, to: AccountInfo<'info>) -> Result<()> {
+        let balance = self.vault.get_lamports();
+        if balance == 0 {
+            msg!("Vault is already empty, skipping the closing transfer");
+            return Ok(());
+        }
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            to,
+            &self.user.key(),
+            &self.vault_state,
+            balance,
+        )
+    }
+
+    /**
+     * @notice Closes vault and splits all remaining funds among the configured beneficiaries
+     * @dev Each beneficiary gets balance * share_bps / BPS_DENOMINATOR; the rounding remainder
+     * goes to the first beneficiary
+     * @param accounts Beneficiary accounts, in the order they were configured
+     * @return Result<()> Success or error
+     */
+    fn distribute(&mut self, accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let shares: Vec<BeneficiaryShare> = self
+            .vault_state
+            .beneficiaries
+            .iter()
+            .copied()
+            .filter(|share| share.beneficiary != Pubkey::default())
+            .collect();
+        require!(accounts.len() == shares.len(), VaultError::BeneficiaryMismatch);
+
+        let balance = self.vault.get_lamports();
+        let amounts: Vec<u64> = shares
+            .iter()
+            .map(|share| {
+                (u128::from(balance) * u128::from(share.share_bps) / u128::from(BPS_DENOMINATOR)) as u64
+            })
+            .collect();
+        let distributed = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(VaultError::MathOverflow)?;
+        let remainder = balance.checked_sub(distributed).ok_or(VaultError::MathOverflow)?;
+
+        for (i, (share, account)) in shares.iter().zip(accounts).enumerate() {
+            require_keys_eq!(account.key(), share.beneficiary, VaultError::BeneficiaryMismatch);
+
+            let amount = if i == 0 {
+                amounts[i].checked_add(remainder).ok_or(VaultError::MathOverflow)?
+            } else {
+                amounts[i]
+            };
+            if amount == 0 {
+                continue;
+            }
+
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                account.clone(),
+                &self.user.key(),
+                &self.vault_state,
+                amount,
+            )?;
+
+            emit!(BeneficiaryPaid {
+                user: self.user.key(),
+                vault: self.vault.key(),
+                beneficiary: share.beneficiary,
+                amount,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Account validation struct for closing a vault into other accounts
+ * @dev Wraps the regular close accounts; the owner still signs and the vault PDAs are still
+ * derived from the owner
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct CloseTo<'info> {
+    pub close: Close<'info>,
+
+    #[account(mut)]
+    pub destination: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub rent_destination: SystemAccount<'info>,
+}
+
+impl<'info> CloseTo<'info> {
+    /**
+     * @notice Moves the vault state rent to the rent destination
+     * @dev The nested close then closes the emptied account to the owner, moving nothing
+     * @return Result<()> Success or error
+     */
+    fn refund_state_rent(&mut self) -> Result<()> {
+        let vault_state = self.close.vault_state.to_account_info();
+        let rent = vault_state.lamports();
+        vault_state.sub_lamports(rent)?;
+        self.rent_destination.add_lamports(rent)?;
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Account validation struct for closing an expired vault
+ * @dev Anyone can sign; the vault PDAs are derived from the owner, who receives everything
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct CloseExpired<'info> {
+    pub caller: Signer<'info>,
+
+    #[account(mut)]
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == owner.key() @ VaultError::NotVaultAuthority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// The vault's token account, required only when closing a token vault
+    #[account(
+        associated_token::mint = vault_state.mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for removing a vault state without a funded vault
+ * @dev The vault PDA must be empty; the state's rent goes back to the owner
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct AbortInitialize<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::NotVaultAuthority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump,
+        constraint = vault.get_lamports() == 0 @ VaultError::VaultFunded
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Only read for its emit_events flag
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+}
+
+impl<'info> CloseExpired<'info> {
+    /**
+     * @notice Drains the vault to the owner after checking nothing still holds it
+     * @param now Current unix timestamp
+     * @return Result<u64> Balance paid to the owner in lamports, or error
+     */
+    fn close_expired(&mut self, now: i64) -> Result<u64> {
+        let vault_state = &self.vault_state;
+        vault_state.require_not_paused(VaultState::PAUSE_CLOSE)?;
+        vault_state.require_not_renounced()?;
+        require!(!vault_state.is_locked(now), VaultError::VaultLocked);
+        require!(vault_state.locked_savings(now) == 0, VaultError::SavingsLocked);
+        require!(vault_state.partially_locked(now) == 0, VaultError::AmountLocked);
+        require!(
+            vault_state.committed_balance(now) == 0
+                && !vault_state.has_active_stream()
+                && vault_state.funding_raised == 0,
+            VaultError::ActiveCommitmentsExist
+        );
+        vault_state.require_tokens_withdrawn(self.vault_token_account.as_ref())?;
+
+        let balance = self.vault.get_lamports();
+        // The rent seeded at initialization was never deposited, so only the balance above it is capped
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        vault_state.require_lifetime_cap(balance.saturating_sub(rent_exempt))?;
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.owner.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            balance,
+        )?;
+        require!(self.vault.get_lamports() == 0, VaultError::CloseIncomplete);
+        self.global_stats.close_vault(balance);
+
+        Ok(balance)
+    }
+}
+
+/**
+ * @notice Account validation struct for draining a vault to its safe address
+ * @dev The destination is pinned to the stored safe address
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct PanicDrain<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == user.key() @ VaultError::NotVaultAuthority,
+        constraint = vault_state.safe_address != Pubkey::default() @ VaultError::SafeAddressNotSet
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut, address = vault_state.safe_address)]
+    pub safe_address: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PanicDrain<'info> {
+    /**
+     * @notice Transfers everything above rent and committed funds to the safe address
+     * @dev Booked like a withdrawal, counting towards the daily and window totals without being
+     * bound by them, and clears the rent buffer it drains
+     * @return Result<u64> Amount drained in lamports or error
+     */
+    fn drain(&mut self) -> Result<u64> {
+        self.vault_state.require_not_renounced()?;
+        let now = self.vault_state.checked_now()?;
+        require!(
+            now.saturating_sub(self.vault_state.safe_address_set_at) >= self.vault_state.safe_address_delay,
+            VaultError::SafeAddressNotTrusted
+        );
+
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        let retained = rent_exempt.saturating_add(self.vault_state.committed_balance(now));
+        let amount = self.vault.get_lamports().saturating_sub(retained);
+        require!(amount > 0, VaultError::OnlyRentRemains);
+
+        msg!("Draining {} lamports from vault: {} to safe address: {}", amount, self.vault.key(), self.safe_address.key());
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.safe_address.to_account_info(),
+            &self.user.key(),
+            &self.vault_state,
+            amount,
+        )?;
+        self.global_stats.record_outflow(amount);
+
+        self.vault_state.record_withdrawal_unchecked(now, amount)?;
+        // The buffer was part of the balance just drained
+        self.vault_state.rent_buffer = 0;
+
+        Ok(amount)
+    }
+}
+
+/**
+ * @notice Account validation struct for syncing a mirror vault
+ * @dev The owner's wallet funds growth and receives the excess, less the withdrawal fee, when the
+ * mirrored vault shrinks
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct SyncMirror<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.mirror_of != Pubkey::default() @ VaultError::MirrorNotSet
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        address = vault_state.mirror_of,
+        constraint = mirrored_vault.key() != vault.key() @ VaultError::InvalidMirrorTarget
+    )]
+    pub mirrored_vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = treasury @ VaultError::InvalidTreasury,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Receives the withdrawal fee on an outflow, if any
+    #[account(mut)]
+    pub treasury: SystemAccount<'info>,
+
+    /// CHECK: Pinned to the instructions sysvar, only read when the config's CPI guard is on
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SyncMirror<'info> {
+    /**
+     * @notice Moves the balance difference so the vault matches the mirrored vault
+     * @dev Deposits from the user when the mirrored vault grew and returns the excess when it shrank;
+     * returning the excess is a withdrawal and passes every withdraw check, pays the fee and counts
+     * towards the withdrawal limits and totals
+     * @param approvals Accounts offered as withdrawal approvers
+     * @return Result<(u64, u64)> Balance of the vault before syncing and the fee paid, or error
+     */
+    fn sync(&mut self, approvals: &[AccountInfo]) -> Result<(u64, u64)> {
+        let current_balance = self.vault.get_lamports();
+        let target_balance = self.mirrored_vault.get_lamports();
+        let mut fee = 0;
+
+        // The mirror must never be drained below its own rent exemption or committed funds
+        let now = self.vault_state.checked_now()?;
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        require!(
+            target_balance >= rent_exempt,
+            VaultError::InsufficientFundsAfterWithdrawal
+        );
+        require!(
+            target_balance >= rent_exempt.saturating_add(self.vault_state.committed_balance(now)),
+            VaultError::FundsCommitted
+        );
+
+        if target_balance > current_balance {
+            let inflow = target_balance.checked_sub(current_balance).ok_or(VaultError::MathOverflow)?;
+            let transfer_accounts = Transfer {
+                from: self.user.to_account_info(),
+                to: self.vault.to_account_info(),
+            };
+
+            let transfer_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+
+            transfer(transfer_ctx, inflow)?;
+            self.global_stats.record_inflow(inflow)?;
+            self.vault_state.credited_total = self.vault_state.credited_total.checked_add(inflow).ok_or(VaultError::MathOverflow)?;
+
+            self.vault_state.record_flow(now, inflow, 0);
+        } else if target_balance < current_balance {
+            let outflow = current_balance.checked_sub(target_balance).ok_or(VaultError::MathOverflow)?;
+            self.vault_state.check_withdrawal(&self.vault, outflow, Some(approvals))?;
+            self.config.require_top_level(&self.instructions.to_account_info())?;
+
+            let (net_amount, outflow_fee) = self.config.withdrawal_split(outflow)?;
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                self.user.to_account_info(),
+                &self.user.key(),
+                &self.vault_state,
+                net_amount,
+            )?;
+
+            if outflow_fee > 0 {
+                transfer_from_vault(
+                    &self.system_program,
+                    &self.vault,
+                    self.treasury.to_account_info(),
+                    &self.user.key(),
+                    &self.vault_state,
+                    outflow_fee,
+                )?;
+            }
+            self.global_stats.record_outflow(outflow);
+
+            self.vault_state.record_withdrawal(now, outflow)?;
+            fee = outflow_fee;
+        }
+
+        Ok((current_balance, fee))
+    }
+}
+
+/**
+ * @notice Account validation struct for claiming from a stream
+ * @dev The recipient signs; the owner key is only used to derive the vault PDAs
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct ClaimStream<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.stream_recipient == recipient.key() @ VaultError::NotStreamRecipient
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimStream<'info> {
+    /**
+     * @notice Transfers vested stream funds to the recipient
+     * @dev Uses PDA signing; amount never exceeds the unclaimed remainder of the stream
+     * @param amount Amount to claim in lamports
+     * @return Result<()> Success or error
+     */
+    fn claim(&mut self, amount: u64) -> Result<()> {
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.recipient.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            amount,
+        )?;
+        self.global_stats.record_outflow(amount);
+
+        self.vault_state.stream_claimed = self
+            .vault_state
+            .stream_claimed
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        let now = self.vault_state.now()?;
+        self.vault_state.record_flow(now, 0, amount);
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Account validation struct for cancelling a stream
+ * @dev Validates the creator owns the vault and the recipient matches the active stream
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct CancelStream<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.has_active_stream() @ VaultError::NoActiveStream
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut, address = vault_state.stream_recipient)]
+    pub recipient: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CancelStream<'info> {
+    /**
+     * @notice Settles the vested portion to the recipient and returns the rest to the creator
+     * @dev Vesting is evaluated at the current clock; already claimed funds are excluded
+     * @return Result<(u64, u64)> Vested amount paid and unvested amount returned, or error
+     */
+    fn cancel(&mut self) -> Result<(u64, u64)> {
+        let now = self.vault_state.checked_now()?;
+        let vested = self.vault_state.stream_claimable(now);
+        let returned = self.vault_state.stream_total.saturating_sub(self.vault_state.stream_vested(now));
+
+        if vested > 0 {
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                self.recipient.to_account_info(),
+                &self.user.key(),
+                &self.vault_state,
+                vested,
+            )?;
+        }
+
+        if returned > 0 {
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                self.user.to_account_info(),
+                &self.user.key(),
+                &self.vault_state,
+                returned,
+            )?;
+        }
+
+        self.global_stats.record_outflow(vested.saturating_add(returned));
+
+        self.vault_state.clear_stream();
+        self.vault_state.record_flow(now, 0, vested.saturating_add(returned));
+
+        Ok((vested, returned))
+    }
+}
+
+/**
+ * @notice Account validation struct for contributing to a funding campaign
+ * @dev Creates the contributor's record on first contribution
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct Contribute<'info> {
+    #[account(mut)]
+    pub contributor: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = contributor,
+        space = Contribution::DISCRIMINATOR.len() + Contribution::INIT_SPACE,
+        seeds = [Contribution::SEED, vault_state.key().as_ref(), contributor.key().as_ref()],
+        bump,
+    )]
+    pub contribution: Account<'info, Contribution>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Contribute<'info> {
+    /**
+     * @notice Transfers a contribution into the vault and records it
+     * @param amount Amount to contribute in lamports
+     * @param bump Bump seed of the contribution PDA
+     * @return Result<()> Success or error
+     */
+    fn contribute(&mut self, amount: u64, bump: u8) -> Result<()> {
+        let transfer_accounts = Transfer {
+            from: self.contributor.to_account_info(),
+            to: self.vault.to_account_info(),
+        };
+
+        let transfer_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+
+        transfer(transfer_ctx, amount)?;
+        self.global_stats.record_inflow(amount)?;
+        self.vault_state.credited_total = self.vault_state.credited_total.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+
+        self.contribution.bump = bump;
+        self.contribution.amount = self
+            .contribution
+            .amount
+            .checked_add(amount)
+            .ok_or(VaultError::InvalidFundingParams)?;
+        self.vault_state.funding_raised = self
+            .vault_state
+            .funding_raised
+            .checked_add(amount)
+            .ok_or(VaultError::InvalidFundingParams)?;
+        let now = self.vault_state.now()?;
+        self.vault_state.record_flow(now, amount, 0);
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Account validation struct for refunding a contribution
+ * @dev Closes the contributor's record and returns its rent to the contributor
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct Refund<'info> {
+    #[account(mut)]
+    pub contributor: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = contributor,
+        seeds = [Contribution::SEED, vault_state.key().as_ref(), contributor.key().as_ref()],
+        bump = contribution.bump
+    )]
+    pub contribution: Account<'info, Contribution>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Refund<'info> {
+    /**
+     * @notice Returns a contribution from the vault to its contributor
+     * @dev Uses PDA signing and removes the amount from the campaign total
+     * @param amount Amount to refund in lamports
+     * @return Result<()> Success or error
+     */
+    fn refund(&mut self, amount: u64) -> Result<()> {
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.contributor.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            amount,
+        )?;
+        self.global_stats.record_outflow(amount);
+
+        self.vault_state.funding_raised = self.vault_state.funding_raised.saturating_sub(amount);
+        let now = self.vault_state.now()?;
+        self.vault_state.record_flow(now, 0, amount);
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Account validation struct for recording an access attempt
+ * @dev The accessor pays for the log on first use; the vault state is only read
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct LogAccess<'info> {
+    #[account(mut)]
+    pub accessor: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init_if_needed,
+        payer = accessor,
+        space = AccessLog::DISCRIMINATOR.len() + AccessLog::INIT_SPACE,
+        seeds = [AccessLog::SEED, vault_state.key().as_ref()],
+        bump,
+    )]
+    pub access_log: Account<'info, AccessLog>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for reading the access log
+ * @dev Read-only; nobody needs to sign
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct ViewAccessLog<'info> {
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [AccessLog::SEED, vault_state.key().as_ref()],
+        bump = access_log.bump
+    )]
+    pub access_log: Account<'info, AccessLog>,
+}
+
+/**
+ * @notice Account validation struct for upgrading a vault state to the current layout
+ * @dev The vault state is taken unchecked because an outdated account is too small to
+ * deserialize; migrate verifies its discriminator before touching it
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct MigrateState<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: Owned by this program at the user's vault state PDA; the discriminator is checked in migrate
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub vault_state: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateState<'info> {
+    /**
+     * @notice Resizes the vault state to the current layout and upgrades its contents
+     * @dev Extra space is zero-filled, so fields an older layout lacked start at zero
+     * until VaultState::upgrade fills in their defaults
+     * @return Result<()> Success or error
+     */
+    fn migrate(&mut self) -> Result<()> {
+        let info = self.vault_state.to_account_info();
+        require!(
+            info.try_borrow_data()?.starts_with(VaultState::DISCRIMINATOR),
+            ErrorCode::AccountDiscriminatorMismatch
+        );
+
+        let current_len = VaultState::DISCRIMINATOR.len() + VaultState::INIT_SPACE;
+        if info.data_len() < current_len {
+            let shortfall = Rent::get()?.minimum_balance(current_len).saturating_sub(info.lamports());
+            if shortfall > 0 {
+                let transfer_accounts = Transfer {
+                    from: self.user.to_account_info(),
+                    to: info.clone(),
+                };
+
+                let transfer_ctx = CpiContext::new(self.system_program.to_account_info(), transfer_accounts);
+
+                transfer(transfer_ctx, shortfall)?;
+            }
+
+            msg!("Resizing vault state: {} from {} to {} bytes", info.key(), info.data_len(), current_len);
+            info.resize(current_len)?;
+        }
+
+        let mut vault_state = VaultState::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        if vault_state.version >= VaultState::VERSION {
+            msg!("Vault state: {} is already at version {}", info.key(), vault_state.version);
+            return Ok(());
+        }
+
+        msg!("Upgrading vault state: {} from version {} to {}", info.key(), vault_state.version, VaultState::VERSION);
+        vault_state.upgrade(self.user.key());
+        let mut data = info.try_borrow_mut_data()?;
+        vault_state.try_serialize(&mut &mut data[..])
+    }
+}
+
+/**
+ * @notice Account validation struct for repairing the stored bump seeds
+ * @dev Both PDAs are found with find_program_address instead of the stored bumps, which may
+ * be stale
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct RefreshBumps<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+        constraint = vault_state.authority == user.key() @ VaultError::NotVaultAuthority
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub vault: SystemAccount<'info>,
+}
+
+/**
+ * @notice Account validation struct for migrating a vault to a successor program
+ * @dev Validates the prepared target program and its destination vault PDA
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct MigrateOut<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.migration_target != Pubkey::default() @ VaultError::MigrationNotPrepared
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    /// CHECK: Only its address and executable flag are used to validate the destination
+    #[account(
+        address = vault_state.migration_target,
+        constraint = target_program.executable @ VaultError::InvalidMigrationTarget
+    )]
+    pub target_program: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = target_program.key()
+    )]
+    pub destination: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateOut<'info> {
+    /**
+     * @notice Transfers the entire vault balance to the successor program's vault
+     * @dev Refuses to move funds still committed to streams or campaigns
+     * @return Result<()> Success or error
+     */
+    fn migrate_out(&mut self) -> Result<()> {
+        self.vault_state.require_not_renounced()?;
+        let now = self.vault_state.checked_now()?;
+        require!(!self.vault_state.is_locked(now), VaultError::VaultLocked);
+        require!(self.vault_state.locked_savings(now) == 0, VaultError::SavingsLocked);
+        require!(self.vault_state.partially_locked(now) == 0, VaultError::AmountLocked);
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        require!(
+            !self.vault_state.goal_locked(self.vault.get_lamports().saturating_sub(rent_exempt)),
+            VaultError::GoalNotReached
+        );
+        require!(
+            self.vault_state.committed_balance(now) == 0,
+            VaultError::FundsCommitted
+        );
+
+        let balance = self.vault.get_lamports();
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.destination.to_account_info(),
+            &self.user.key(),
+            &self.vault_state,
+            balance,
+        )?;
+        self.global_stats.close_vault(balance);
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Account validation struct for accepting a vault ownership transfer
+ * @dev The pending authority signs and funds its new vault state; the old one is closed to the owner,
+ * who does not sign
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct AcceptOwnership<'info> {
+    #[account(mut)]
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == owner.key() @ VaultError::NotVaultAuthority
+    )]
+    pub vault_state: Box<Account<'info, VaultState>>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = vault_state.pending_authority != Pubkey::default() @ VaultError::NotPendingAuthority,
+        address = vault_state.pending_authority @ VaultError::NotPendingAuthority
+    )]
+    pub new_owner: Signer<'info>,
+
+    #[account(
+        init,
+        payer = new_owner,
+        space = VaultState::DISCRIMINATOR.len() + VaultState::INIT_SPACE,
+        seeds = [VaultState::STATE_SEED, new_owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub new_vault_state: Box<Account<'info, VaultState>>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, new_owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub new_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptOwnership<'info> {
+    /**
+     * @notice Copies the vault state to the new authority's PDA and moves the whole balance along
+     * @dev Refuses to move funds committed to streams or campaigns, whose accounts reference the old state;
+     * the pending transfer is cleared in the copied state
+     * @param bumps Bumps of the new authority's PDAs
+     * @return Result<()> Success or error
+     */
+    fn accept_ownership(&mut self, bumps: &AcceptOwnershipBumps) -> Result<()> {
+        let now = self.vault_state.checked_now()?;
+        require!(
+            self.vault_state.committed_balance(now) == 0 && !self.vault_state.has_active_stream(),
+            VaultError::ActiveCommitmentsExist
+        );
+        require_keys_eq!(self.vault_state.mint, Pubkey::default(), VaultError::TokenVaultNotTransferable);
+
+        let mut state = (**self.vault_state).clone();
+        state.state_bump = bumps.new_vault_state;
+        state.vault_bump = bumps.new_vault;
+        state.authority = self.new_owner.key();
+        state.pending_authority = Pubkey::default();
+        self.new_vault_state.set_inner(state);
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.new_vault.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            self.vault.get_lamports(),
+        )
+    }
+}
+
+/**
+ * @notice Account validation struct for starting a recovery
+ * @dev The guardian signs; the vault PDAs are derived from the owner, who does not sign
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct InitiateRecovery<'info> {
+    pub guardian: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == owner.key() @ VaultError::NotVaultAuthority,
+        constraint = vault_state.guardian != Pubkey::default() @ VaultError::NotVaultGuardian,
+        constraint = vault_state.guardian == guardian.key() @ VaultError::NotVaultGuardian
+    )]
+    pub vault_state: Account<'info, VaultState>,
+}
+
+/**
+ * @notice Account validation struct for completing a recovery
+ * @dev Creates the new authority's vault state, funded by the new authority, and closes the old one
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct FinalizeRecovery<'info> {
+    pub guardian: Signer<'info>,
+
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        close = new_owner,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.authority == owner.key() @ VaultError::NotVaultAuthority,
+        constraint = vault_state.guardian != Pubkey::default() @ VaultError::NotVaultGuardian,
+        constraint = vault_state.guardian == guardian.key() @ VaultError::NotVaultGuardian,
+        constraint = vault_state.has_pending_recovery() @ VaultError::NoRecoveryPending
+    )]
+    pub vault_state: Box<Account<'info, VaultState>>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(mut, address = vault_state.recovery_authority @ VaultError::InvalidNewAuthority)]
+    pub new_owner: Signer<'info>,
+
+    #[account(
+        init,
+        payer = new_owner,
+        space = VaultState::DISCRIMINATOR.len() + VaultState::INIT_SPACE,
+        seeds = [VaultState::STATE_SEED, new_owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub new_vault_state: Box<Account<'info, VaultState>>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, new_owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub new_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> FinalizeRecovery<'info> {
+    /**
+     * @notice Copies the vault state to the new authority's PDA and moves the whole balance along
+     * @dev Same restrictions as AcceptOwnership::accept_ownership; the recovery and any pending
+     * transfer are cleared in the copied state
+     * @param bumps Bumps of the new authority's PDAs
+     * @return Result<()> Success or error
+     */
+    fn finalize_recovery(&mut self, bumps: &FinalizeRecoveryBumps) -> Result<()> {
+        let now = self.vault_state.checked_now()?;
+        require!(
+            self.vault_state.committed_balance(now) == 0 && !self.vault_state.has_active_stream(),
+            VaultError::ActiveCommitmentsExist
+        );
+        require_keys_eq!(self.vault_state.mint, Pubkey::default(), VaultError::TokenVaultNotTransferable);
+
+        let mut state = (**self.vault_state).clone();
+        state.state_bump = bumps.new_vault_state;
+        state.vault_bump = bumps.new_vault;
+        state.authority = self.new_owner.key();
+        state.clear_recovery();
+        state.pending_authority = Pubkey::default();
+        self.new_vault_state.set_inner(state);
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.new_vault.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            self.vault.get_lamports(),
+        )
+    }
+}
+
+/**
+ * @notice Account validation struct for granting spending authority
+ * @dev Creates the grant on first use and overwrites it on later grants to the same grantee
+ */
+#[derive(Accounts)]
+#[instruction(index: u64, grantee: Pubkey)]
+pub struct GrantSpending<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = SpendingGrant::DISCRIMINATOR.len() + SpendingGrant::INIT_SPACE,
+        seeds = [SpendingGrant::SEED, vault_state.key().as_ref(), grantee.as_ref()],
+        bump,
+    )]
+    pub grant: Account<'info, SpendingGrant>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for spending against a grant
+ * @dev The grantee signs; the owner receives the grant's rent when it is exhausted
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct SpendGranted<'info> {
+    #[account(mut)]
+    pub grantee: Signer<'info>,
+
+    #[account(mut)]
+    pub owner: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, owner.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [SpendingGrant::SEED, vault_state.key().as_ref(), grantee.key().as_ref()],
+        bump = grant.bump
+    )]
+    pub grant: Account<'info, SpendingGrant>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = treasury @ VaultError::InvalidTreasury,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    /// Receives the withdrawal fee, if any
+    #[account(mut)]
+    pub treasury: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SpendGranted<'info> {
+    /**
+     * @notice Transfers granted funds from the vault to the grantee, less the withdrawal fee, and draws down the grant
+     * @dev The grant is drawn down by the full amount, fee included
+     * @param amount Amount to withdraw in lamports, including the fee
+     * @param now Current unix timestamp
+     * @return Result<u64> Fee paid to the treasury in lamports, or error
+     */
+    fn spend(&mut self, amount: u64, now: i64) -> Result<u64> {
+        let (net_amount, fee) = self.config.withdrawal_split(amount)?;
+
+        transfer_from_vault(
+            &self.system_program,
+            &self.vault,
+            self.grantee.to_account_info(),
+            &self.owner.key(),
+            &self.vault_state,
+            net_amount,
+        )?;
+
+        if fee > 0 {
+            transfer_from_vault(
+                &self.system_program,
+                &self.vault,
+                self.treasury.to_account_info(),
+                &self.owner.key(),
+                &self.vault_state,
+                fee,
+            )?;
+        }
+        self.global_stats.record_outflow(amount);
+
+        self.grant.spent = self.grant.spent.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+        self.vault_state.record_withdrawal(now, amount)?;
+
+        Ok(fee)
+    }
+}
+
+/**
+ * @notice Account validation struct for tying a vault to an SPL mint
+ * @dev Creates the vault PDA's associated token account for the mint
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct InitializeToken<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for SPL token deposits and withdrawals
+ * @dev The user's token account must hold the vault's mint
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct TokenPayment<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+        constraint = vault_state.mint != Pubkey::default() @ VaultError::NotTokenVault
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == vault_state.mint @ VaultError::MintMismatch
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = vault_state.mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        constraint = !config.paused @ VaultError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'info> TokenPayment<'info> {
+    /**
+     * @notice Transfers tokens from the user's token account to the vault's
+     * @param amount Amount to deposit in base units of the mint
+     * @return Result<()> Success or error
+     */
+    fn deposit(&mut self, amount: u64) -> Result<()> {
+        let transfer_accounts = token::Transfer {
+            from: self.user_token_account.to_account_info(),
+            to: self.vault_token_account.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+
+        let transfer_ctx = CpiContext::new(self.token_program.to_account_info(), transfer_accounts);
+
+        token::transfer(transfer_ctx, amount)
+    }
+
+    /**
+     * @notice Transfers tokens from the vault's token account to the user's
+     * @dev Uses PDA signing with the vault as the token account authority
+     * @param amount Amount to withdraw in base units of the mint
+     * @return Result<()> Success or error
+     */
+    fn withdraw(&mut self, amount: u64) -> Result<()> {
+        let user_key = self.user.key();
+        let index_seed = self.vault_state.index.to_le_bytes();
+        let seeds = &[VaultState::VAULT_SEED, user_key.as_ref(), &index_seed, &[self.vault_state.vault_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_accounts = token::Transfer {
+            from: self.vault_token_account.to_account_info(),
+            to: self.user_token_account.to_account_info(),
+            authority: self.vault.to_account_info(),
+        };
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            transfer_accounts,
+            signer_seeds,
+        );
+
+        token::transfer(transfer_ctx, amount)
+    }
+}
+
+/**
+ * @notice Account validation struct for wSOL deposits
+ * @dev Wraps the regular deposit accounts; the unwrap account only lives for the instruction
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct DepositWsol<'info> {
+    pub payment: Payment<'info>,
+
+    #[account(address = token::spl_token::native_mint::ID @ VaultError::NotNativeMint)]
+    pub native_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_wsol.mint == native_mint.key() @ VaultError::NotNativeMint,
+        token::authority = payment.user,
+    )]
+    pub user_wsol: Account<'info, TokenAccount>,
+
+    /// Vault-owned wSOL account, closed to the user before the instruction ends
+    #[account(
+        init,
+        payer = payment.user,
+        token::mint = native_mint,
+        token::authority = payment.vault,
+        seeds = [WSOL_UNWRAP_SEED, payment.vault.key().as_ref()],
+        bump,
+    )]
+    pub unwrap_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> DepositWsol<'info> {
+    /**
+     * @notice Turns the user's wSOL back into native lamports held by the user
+     * @dev Closing a native token account releases its whole balance, rent included, so the
+     * user gets back the rent paid for the unwrap account along with the amount
+     * @param amount Amount of wSOL to unwrap, in lamports
+     * @return Result<()> Success or error
+     */
+    fn unwrap(&mut self, amount: u64) -> Result<()> {
+        let transfer_accounts = token::Transfer {
+            from: self.user_wsol.to_account_info(),
+            to: self.unwrap_account.to_account_info(),
+            authority: self.payment.user.to_account_info(),
+        };
+        token::transfer(CpiContext::new(self.token_program.to_account_info(), transfer_accounts), amount)?;
+
+        let user_key = self.payment.user.key();
+        let index_seed = self.payment.vault_state.index.to_le_bytes();
+        let seeds = &[VaultState::VAULT_SEED, user_key.as_ref(), &index_seed, &[self.payment.vault_state.vault_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let close_accounts = token::CloseAccount {
+            account: self.unwrap_account.to_account_info(),
+            destination: self.payment.user.to_account_info(),
+            authority: self.payment.vault.to_account_info(),
+        };
+        token::close_account(CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            close_accounts,
+            signer_seeds,
+        ))
+    }
+}
+
+/**
+ * @notice Account validation struct for setting the reward rate
+ * @dev Validates the signer against the config admin; creates the reward config on first use
+ */
+#[derive(Accounts)]
+pub struct SetRewardRate<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = admin @ VaultError::NotConfigAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = RewardConfig::DISCRIMINATOR.len() + RewardConfig::INIT_SPACE,
+        seeds = [RewardConfig::SEED],
+        bump,
+    )]
+    pub reward_config: Account<'info, RewardConfig>,
+
+    #[account(
+        seeds = [RewardConfig::TREASURY_SEED],
+        bump,
+    )]
+    pub reward_treasury: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for accruing rewards into the default vault
+ * @dev The reward treasury is a system-owned PDA, so the program can sign transfers out of it
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct Accrue<'info> {
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::STATE_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.state_bump,
+    )]
+    pub vault_state: Account<'info, VaultState>,
+
+    #[account(
+        mut,
+        seeds = [VaultState::VAULT_SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump = vault_state.vault_bump,
+    )]
+    pub vault: SystemAccount<'info>,
+
+    #[account(
+        seeds = [RewardConfig::SEED],
+        bump = reward_config.bump,
+    )]
+    pub reward_config: Account<'info, RewardConfig>,
+
+    #[account(
+        mut,
+        seeds = [RewardConfig::TREASURY_SEED],
+        bump = reward_config.treasury_bump,
+    )]
+    pub reward_treasury: SystemAccount<'info>,
+
+    /// Protocol-wide vault count and TVL
+    #[account(
+        mut,
+        seeds = [GlobalStats::SEED],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Accrue<'info> {
+    /**
+     * @notice Computes the reward since last_accrual, moves it into the vault and restarts the clock
+     * @return Result<u64> Reward paid in lamports, or MathOverflow
+     */
+    fn accrue(&mut self) -> Result<u64> {
+        let now = self.vault_state.checked_now()?;
+        let last_accrual = self.vault_state.last_accrual;
+        self.vault_state.last_accrual = now;
+        if last_accrual == 0 {
+            return Ok(0);
+        }
+
+        let elapsed = now.saturating_sub(last_accrual).max(0) as u128;
+        let rent_exempt = Rent::get()?.minimum_balance(self.vault.to_account_info().data_len());
+        let balance = u128::from(self.vault.get_lamports().saturating_sub(rent_exempt));
+
+        let earned = elapsed
+            .checked_mul(u128::from(self.reward_config.rate))
+            .and_then(|v| v.checked_mul(balance))
+            .ok_or(VaultError::MathOverflow)?
+            / REWARD_RATE_SCALE;
+        let reward = u64::try_from(earned)
+            .map_err(|_| VaultError::MathOverflow)?
+            .min(self.reward_treasury.get_lamports());
+
+        if reward > 0 {
+            let seeds = &[RewardConfig::TREASURY_SEED, &[self.reward_config.treasury_bump]];
+            let signer_seeds = &[&seeds[..]];
+
+            let transfer_ctx = CpiContext::new_with_signer(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.reward_treasury.to_account_info(),
+                    to: self.vault.to_account_info(),
+                },
+                signer_seeds,
+            );
+            transfer(transfer_ctx, reward)?;
+            self.global_stats.record_inflow(reward)?;
+            self.vault_state.credited_total = self.vault_state.credited_total.checked_add(reward).ok_or(VaultError::MathOverflow)?;
+        }
+
+        emit!(RewardAccrued {
+            user: self.user.key(),
+            vault: self.vault.key(),
+            amount: reward,
+            elapsed: elapsed as i64,
+        });
+
+        Ok(reward)
+    }
+}
+
+/**
+ * @notice Account validation struct for configuring the deposit match
+ * @dev Validates the signer against the config admin; creates the match config on first use
+ */
+#[derive(Accounts)]
+pub struct SetDepositMatch<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [Config::SEED],
+        bump = config.bump,
+        has_one = admin @ VaultError::NotConfigAdmin
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = MatchConfig::DISCRIMINATOR.len() + MatchConfig::INIT_SPACE,
+        seeds = [MatchConfig::SEED],
+        bump,
+    )]
+    pub match_config: Account<'info, MatchConfig>,
+
+    #[account(
+        seeds = [MatchConfig::TREASURY_SEED],
+        bump,
+    )]
+    pub match_treasury: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/**
+ * @notice Account validation struct for matched deposits
+ * @dev Wraps the regular deposit accounts; the match treasury is a system-owned PDA, so the
+ * program can sign transfers out of it
+ */
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct MatchedPayment<'info> {
+    pub payment: Payment<'info>,
+
+    #[account(
+        mut,
+        seeds = [MatchConfig::SEED],
+        bump = match_config.bump,
+    )]
+    pub match_config: Account<'info, MatchConfig>,
+
+    #[account(
+        mut,
+        seeds = [MatchConfig::TREASURY_SEED],
+        bump = match_config.treasury_bump,
+    )]
+    pub match_treasury: SystemAccount<'info>,
+}
+
+impl<'info> MatchedPayment<'info> {
+    /**
+     * @notice Moves the match for a deposit from the match treasury into the vault
+     * @dev Draws down the campaign budget and the vault's matched_total
+     * @param amount Amount the user deposited in lamports
+     * @return Result<u64> Match paid in lamports, or MathOverflow
+     */
+    fn match_deposit(&mut self, amount: u64) -> Result<u64> {
+        let vault_state = &self.payment.vault_state;
+        let vault_remaining = if self.match_config.per_vault_cap == 0 {
+            u64::MAX
+        } else {
+            self.match_config.per_vault_cap.saturating_sub(vault_state.matched_total)
+        };
+
+        let matched_amount =
+            (u128::from(amount) * u128::from(self.match_config.ratio_bps) / u128::from(BPS_DENOMINATOR)) as u64;
+        let matched_amount = matched_amount
+            .min(self.match_config.budget)
+            .min(vault_remaining)
+            .min(self.match_treasury.get_lamports());
+        if matched_amount == 0 {
+            return Ok(0);
+        }
+
+        self.match_config.budget = self.match_config.budget.checked_sub(matched_amount).ok_or(VaultError::MathOverflow)?;
+        let vault_state = &mut self.payment.vault_state;
+        vault_state.matched_total = vault_state.matched_total.checked_add(matched_amount).ok_or(VaultError::MathOverflow)?;
+
+        let seeds = &[MatchConfig::TREASURY_SEED, &[self.match_config.treasury_bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            self.payment.system_program.to_account_info(),
+            Transfer {
+                from: self.match_treasury.to_account_info(),
+                to: self.payment.vault.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer(transfer_ctx, matched_amount)?;
+        self.payment.global_stats.record_inflow(matched_amount)?;
+
+        Ok(matched_amount)
+    }
+}
+
+/**
+ * @notice Tops a new vault PDA up to its rent-exempt minimum
+ * @dev Skips the transfer when the vault PDA already holds it, as when left funded by a prior life
+ * @param system_program System program used for the transfer CPI
+ * @param vault Vault PDA being seeded
+ * @param payer Account paying the shortfall
+ * @return Result<()> Success or error
+ */
+fn seed_vault<'info>(
+    system_program: &Program<'info, System>,
+    vault: &SystemAccount<'info>,
+    payer: AccountInfo<'info>,
+) -> Result<()> {
+    let rent_exempt = Rent::get()?.minimum_balance(vault.to_account_info().data_len());
+    let shortfall = rent_exempt.saturating_sub(vault.get_lamports());
+    if shortfall == 0 {
+        msg!("Vault already holds its rent-exempt minimum, skipping the seeding transfer");
+        return Ok(());
+    }
+
+    let transfer_accounts = Transfer {
+        from: payer,
+        to: vault.to_account_info(),
+    };
+
+    let transfer_ctx = CpiContext::new(system_program.to_account_info(), transfer_accounts);
+
+    transfer(transfer_ctx, shortfall)
+}
+
+/**
+ * @notice Transfers lamports out of a vault PDA
+ * @dev Signs the system transfer with the vault seeds derived from the owner key and vault index
+ * @param system_program System program used for the transfer CPI
+ * @param vault Vault PDA the lamports are taken from
+ * @param to Account receiving the lamports
+ * @param owner Owner key the vault PDA is derived from
+ * @param vault_state State of the vault, holding its index and bump seed
+ * @param amount Amount to transfer in lamports
+ * @return Result<()> Success or error
+ */
+fn transfer_from_vault<'info>(
+    system_program: &Program<'info, System>,
+    vault: &SystemAccount<'info>,
+    to: AccountInfo<'info>,
+    owner: &Pubkey,
+    vault_state: &VaultState,
+    amount: u64,
+) -> Result<()> {
+    let transfer_accounts = Transfer {
+        from: vault.to_account_info(),
+        to,
+    };
+
+    // Create PDA seeds for vault signing
+    let index_seed = vault_state.index.to_le_bytes();
+    let seeds = &[VaultState::VAULT_SEED, owner.as_ref(), &index_seed, &[vault_state.vault_bump]];
+    let signer_seeds = &[&seeds[..]];
+
+    let transfer_ctx = CpiContext::new_with_signer(
+        system_program.to_account_info(),
+        transfer_accounts,
+        signer_seeds,
+    );
+
+    transfer(transfer_ctx, amount)
+}
+
+/**
+ * @notice Closes a vault and pays out its balance, shared by close, confirm_close and close_to
+ * @dev The close delay is checked after the lock, savings, goal and funding checks, so a
+ * blocked close reports why it is blocked before reporting the delay
+ * @param accounts Close accounts
+ * @param remaining_accounts Beneficiary accounts, in the order they were configured
+ * @param index Index of the vault among the user's vaults
+ * @param force_close Cancel an active stream and settle its vested portion before closing
+ * @param confirmed Whether the close confirms a pending close request
+ * @param destination Account receiving the balance when there are no beneficiaries
+ * @return Result<CloseSettlement> Owner and final balance paid out, or error
+ */
+fn close_vault<'info>(
+    accounts: &mut Close<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    index: u64,
+    force_close: bool,
+    confirmed: bool,
+    destination: AccountInfo<'info>,
+) -> Result<CloseSettlement> {
+    accounts.vault_state.require_not_paused(VaultState::PAUSE_CLOSE)?;
+    accounts.vault_state.require_not_renounced()?;
+    let now = accounts.vault_state.checked_now()?;
+    let vault_state = &accounts.vault_state;
+    require!(!vault_state.is_locked(now), VaultError::VaultLocked);
+    require!(vault_state.locked_savings(now) == 0, VaultError::SavingsLocked);
+    require!(vault_state.partially_locked(now) == 0, VaultError::AmountLocked);
+
+    // Everything in the vault leaves it, including the vested stream settled below
+    let opening_balance = accounts.vault.get_lamports();
+    let rent_exempt = Rent::get()?.minimum_balance(accounts.vault.to_account_info().data_len());
+    require!(
+        !vault_state.goal_locked(opening_balance.saturating_sub(rent_exempt)),
+        VaultError::GoalNotReached
+    );
+
+    // Contributions can only be returned by each contributor's refund, so force can't settle them
+    require!(
+        vault_state.funding_succeeded(now) || vault_state.funding_raised == 0,
+        VaultError::ActiveCommitmentsExist
+    );
+
+    vault_state.require_close_allowed(now, confirmed)?;
+    vault_state.require_tokens_withdrawn(accounts.vault_token_account.as_ref())?;
+
+    if vault_state.has_active_stream() {
+        require!(force_close, VaultError::ActiveCommitmentsExist);
+
+        msg!("Force closing: cancelling active stream on vault: {}", accounts.vault.key());
+        let (vested, returned) = accounts.settle_stream(now)?;
+
+        emit!(StreamCancelled {
+            user: accounts.user.key(),
+            vault: accounts.vault.key(),
+            recipient: accounts.vault_state.stream_recipient,
+            vested,
+            returned,
+        });
+
+        accounts.vault_state.clear_stream();
+    }
+
+    let vault_balance = accounts.vault.get_lamports();
+    // The rent seeded at initialization was never deposited, so only the balance above it is capped
+    accounts.vault_state.require_lifetime_cap(vault_balance.saturating_sub(rent_exempt))?;
+    
+    msg!("Closing vault {}: {} with balance: {}", index, accounts.vault.key(), vault_balance);
+    let destination = if accounts.vault_state.has_beneficiaries() {
+        accounts.distribute(remaining_accounts)?;
+        Pubkey::default()
+    } else {
+        let destination_key = destination.key();
+        accounts.close(destination)?;
+        destination_key
+    };
+    // The vault state rent is refunded by the close constraint on exit, once the vault is empty
+    require!(accounts.vault.get_lamports() == 0, VaultError::CloseIncomplete);
+    accounts.vault_state.assert_invariants(&accounts.vault)?;
+    accounts.global_stats.close_vault(opening_balance);
+    
+    if accounts.config.emit_events {
+        let clock = Clock::get()?;
+        emit!(VaultClosed {
+            event_version: EVENT_VERSION,
+            category: VaultClosed::CATEGORY,
+            user: accounts.user.key(),
+            vault: accounts.vault.key(),
+            destination,
+            final_balance: vault_balance,
+            timestamp: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+    }
+    
+    Ok(CloseSettlement {
+        owner: accounts.user.key(),
+        final_balance: vault_balance,
+    })
+}
+
+/**
+ * @notice Vault state account data structure
+ * @dev Stores bump seeds for PDA derivation and per-vault settings
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct VaultState {
+    /// Bump seed for vault state PDA
+    pub state_bump: u8,
+    /// Bump seed for vault PDA
+    pub vault_bump: u8,
+    /// Minimum amount for a single withdrawal (0 disables the check)
+    pub min_withdrawal: u64,
+    /// Opaque off-chain reference ID (e.g. a backend account ID), not interpreted on-chain
+    pub external_ref: u64,
+    /// Vault whose balance this vault mirrors (default pubkey when not a mirror)
+    pub mirror_of: Pubkey,
+    /// Keys allowed to approve large withdrawals (default pubkey marks an empty slot)
+    pub approvers: [Pubkey; VaultState::MAX_APPROVERS],
+    /// Distinct approver signatures required above the threshold (0 disables approvals)
+    pub required_approvals: u8,
+    /// Withdrawals above this amount require approvals
+    pub approval_threshold: u64,
+    /// Recipient of the active stream (default pubkey when no stream is active)
+    pub stream_recipient: Pubkey,
+    /// Total amount deposited into the active stream
+    pub stream_total: u64,
+    /// Amount of the stream that vests each second
+    pub stream_rate: u64,
+    /// Unix timestamp the stream started vesting
+    pub stream_start: i64,
+    /// Amount of the stream already claimed by the recipient
+    pub stream_claimed: u64,
+    /// Contributions required for the funding campaign to succeed (0 when no campaign)
+    pub funding_goal: u64,
+    /// Unix timestamp at which the funding campaign ends
+    pub funding_deadline: i64,
+    /// Contributions currently held for the funding campaign
+    pub funding_raised: u64,
+    /// Slot in which the vault was created, for locating the creation transaction
+    pub created_slot: u64,
+    /// Ring buffer of daily inflow and outflow, indexed by day modulo FLOW_DAYS
+    pub flow_buckets: [FlowBucket; VaultState::FLOW_DAYS],
+    /// Successor program prepared to receive the vault (default pubkey when none)
+    pub migration_target: Pubkey,
+    /// Bitmask of paused instructions, see the VaultState::PAUSE_* bits
+    pub paused_instructions: u8,
+    /// Whether deposit and withdraw emit CompactEvent instead of the full events
+    pub compact_events: bool,
+    /// Unix timestamp at which the vault was created
+    pub created_at: i64,
+    /// Latest plausible clock timestamp for time-sensitive checks (0 disables the bound)
+    pub max_timestamp: i64,
+    /// Deposits above this amount must be proposed and confirmed (0 disables)
+    pub confirm_threshold: u64,
+    /// Seconds after a proposal within which it can be confirmed
+    pub confirm_window: i64,
+    /// Amount of the pending deposit proposal (0 when none)
+    pub proposed_deposit: u64,
+    /// Unix timestamp of the pending deposit proposal
+    pub proposed_at: i64,
+    /// Account named in SuspiciousActivity events (default disables monitoring)
+    pub emergency_contact: Pubkey,
+    /// Withdrawals above this share of the balance in basis points are flagged (0 disables)
+    pub large_withdrawal_bps: u16,
+    /// Number of withdrawals within rapid_window that is flagged (0 disables)
+    pub rapid_withdrawal_count: u8,
+    /// Length of the rapid withdrawal window in seconds
+    pub rapid_window: i64,
+    /// Unix timestamp at which the current rapid withdrawal window started
+    pub rapid_window_start: i64,
+    /// Withdrawals seen in the current rapid withdrawal window
+    pub rapid_window_withdrawals: u8,
+    /// Non-monetary loyalty points accrued from deposits
+    pub reward_points: u64,
+    /// Lamports deposited per reward point (0 disables accrual)
+    pub lamports_per_point: u64,
+    /// Lifetime total deposited by the owner, including stream funding, in lamports
+    pub total_deposited: u64,
+    /// Lifetime deposit total above which ReportingThresholdCrossed is emitted (0 disables)
+    pub reporting_threshold: u64,
+    /// Whether the current reporting threshold has already been reported
+    pub threshold_reported: bool,
+    /// Unix timestamp of the last deposit into the vault
+    pub last_deposit_time: i64,
+    /// Withdrawals require a deposit within this many seconds (0 disables)
+    pub deposit_recency: i64,
+    /// Unix timestamp before which funds cannot leave the vault (0 when unlocked)
+    pub unlock_time: i64,
+    /// Short categorization tags, zero-padded; an all-zero slot is empty
+    pub tags: [[u8; TAG_LEN]; MAX_TAGS],
+    /// Lifetime total withdrawn by the owner in lamports
+    pub total_withdrawn: u64,
+    /// SPL mint the vault's token account holds (default for SOL-only vaults)
+    pub mint: Pubkey,
+    /// Maximum withdrawn per UTC day in lamports (0 when unlimited)
+    pub daily_limit: u64,
+    /// Amount withdrawn on last_withdraw_day in lamports
+    pub withdrawn_today: u64,
+    /// Day number (unix timestamp / SECONDS_PER_DAY) of the last withdrawal
+    pub last_withdraw_day: i64,
+    /// Shares the final balance is split into on close (empty slots have a default beneficiary)
+    pub beneficiaries: [BeneficiaryShare; VaultState::MAX_BENEFICIARIES],
+    /// Wallet allowed to operate the vault; the PDAs are always seeded by this key
+    pub authority: Pubkey,
+    /// Address panic_drain sends funds to (default when unset)
+    pub safe_address: Pubkey,
+    /// Unix timestamp the safe address was last changed
+    pub safe_address_set_at: i64,
+    /// Seconds a newly set safe address must age before panic_drain trusts it
+    pub safe_address_delay: i64,
+    /// Maximum balance above the rent-exempt minimum in lamports (0 when uncapped)
+    pub max_balance: u64,
+    /// Set by initialize; false only on a freshly created, not yet initialized account
+    pub is_initialized: bool,
+    /// Share of total_deposited withdrawals must leave in the vault, in basis points (0 disables)
+    pub floor_bps: u16,
+    /// Maximum balance floor in lamports (0 when uncapped)
+    pub floor_cap: u64,
+    /// Unix timestamp of the last owner deposit or withdrawal, for spotting dormant vaults
+    pub last_activity: i64,
+    /// Account allowed to withdraw to the owner via withdraw_as_delegate (default when unset)
+    pub delegate: Pubkey,
+    /// Seconds withdraw_with_save locks its diverted savings for (0 disables withdraw_with_save)
+    pub savings_lock_period: i64,
+    /// Savings diverted by withdraw_with_save, locked until savings_unlock_time
+    pub savings_tranche: u64,
+    /// Unix timestamp from which the savings tranche can leave the vault
+    pub savings_unlock_time: i64,
+    /// Minimum amount accepted by a single deposit in lamports
+    pub min_deposit: u64,
+    /// Display name for the vault, zero-padded UTF-8 (all zeros when unlabeled)
+    pub label: [u8; LABEL_LEN],
+    /// Index distinguishing this vault from the owner's other vaults (0 for the default vault)
+    pub index: u64,
+    /// Account allowed to start a recovery to a new authority (default when unset)
+    pub guardian: Pubkey,
+    /// Authority a pending recovery hands the vault to (default when none is pending)
+    pub recovery_authority: Pubkey,
+    /// Unix timestamp the pending recovery was started at
+    pub recovery_initiated_at: i64,
+    /// Balance above rent the vault must reach before funds can leave it (0 for no goal)
+    pub goal_amount: u64,
+    /// Whether a deposit has reached the current goal, unlocking the vault for good
+    pub goal_reached: bool,
+    /// Unix timestamp the vesting window started at
+    pub vesting_start: i64,
+    /// Unix timestamp from which the vested balance is fully released (0 when never vested)
+    pub vesting_end: i64,
+    /// Withdrawable balance snapshotted by start_vesting and released over the window
+    pub vesting_total: u64,
+    /// Amount withdrawn since the vesting window started
+    pub vesting_withdrawn: u64,
+    /// Maximum amount a single withdrawal can take in lamports
+    pub max_withdrawal: u64,
+    /// Seconds a close request must wait before confirm_close (0 allows the single-step close)
+    pub close_delay: i64,
+    /// Unix timestamp of the pending close request (0 when none is pending)
+    pub close_requested_at: i64,
+    /// Maximum withdrawals per WITHDRAWAL_WINDOW (0 when unlimited)
+    pub max_withdrawals_per_window: u32,
+    /// Withdrawals made in the current window
+    pub withdraw_count_window: u32,
+    /// Unix timestamp the current withdrawal window started at
+    pub window_start: i64,
+    /// Unix timestamp rewards were last accrued up to (0 before the first accrual)
+    pub last_accrual: i64,
+    /// Whether the owner permanently gave up withdrawing and closing, see renounce_withdrawals
+    pub withdrawals_renounced: bool,
+    /// Fixed amount withdrawals keep above rent and committed funds, part of the balance floor
+    pub reserve: u64,
+    /// Lamports set_partial_lock keeps in the vault until partial_unlock_time
+    pub locked_amount: u64,
+    /// Unix timestamp from which locked_amount can be withdrawn (0 when unset)
+    pub partial_unlock_time: i64,
+    /// Whether withdraw closes the vault once only rent is left, see set_auto_close
+    pub auto_close: bool,
+    /// Withdrawal queued by schedule_withdrawal (zero amount when none is pending)
+    pub scheduled_withdrawal: ScheduledWithdrawal,
+    /// Unix timestamp after which anyone can close the vault to its owner (0 for never)
+    pub expires_at: i64,
+    /// Authority proposed by transfer_ownership, waiting to accept (default when none is pending)
+    pub pending_authority: Pubkey,
+    /// Maximum total_withdrawn owner withdrawals may ever reach (0 for unlimited)
+    pub lifetime_withdraw_cap: u64,
+    /// Lamports added by fund_rent_buffer, part of the balance floor until the vault closes
+    pub rent_buffer: u64,
+    /// Lamports deposit_matched has paid into this vault, counted against MatchConfig::per_vault_cap
+    pub matched_total: u64,
+    /// Lamports credited to the vault outside deposits and matches: accrued rewards, mirror syncs, contributions
+    /// and lamports sent to the vault PDA directly, once withdrawn
+    pub credited_total: u64,
+    /// Whether the owner lets the config admin withdraw on their behalf, see set_admin_managed
+    pub admin_managed: bool,
+    /// Raised or removed daily limit waiting for daily_limit_change_at (0 removes the cap)
+    pub pending_daily_limit: u64,
+    /// Timestamp from which pending_daily_limit replaces daily_limit (0 when nothing is pending)
+    pub daily_limit_change_at: i64,
+    /// Layout version of this account, see VaultState::VERSION (0 before versioning)
+    pub version: u8,
+    /// Timestamp reported by now() instead of the clock (0 when unset), test-mode builds only
+    #[cfg(feature = "test-mode")]
+    pub clock_override: i64,
+}
+
+impl VaultState {
+    /// Current layout version, bumped whenever fields are appended; migrate upgrades older accounts
+    pub const VERSION: u8 = 16;
+    /// Seed constant for vault state PDA
+    pub const STATE_SEED: &'static [u8] = b"state";
+    /// Seed constant for vault PDA
+    pub const VAULT_SEED: &'static [u8] = b"vault";
+    /// Maximum number of withdrawal approvers
+    pub const MAX_APPROVERS: usize = 5;
+    /// Maximum number of beneficiaries sharing the balance on close
+    pub const MAX_BENEFICIARIES: usize = 5;
+    /// Number of daily flow buckets kept in the ring buffer
+    pub const FLOW_DAYS: usize = 7;
+    /// Pause bit for deposit, batch_deposit, round_up_deposit, pay_and_save, propose_deposit, confirm_deposit and deposit_token
+    pub const PAUSE_DEPOSIT: u8 = 1 << 0;
+    /// Pause bit for withdraw, withdraw_to, withdraw_to_whitelisted, withdraw_percent, withdraw_with_save, drain, withdraw_as_delegate, spend_granted and withdraw_token
+    pub const PAUSE_WITHDRAW: u8 = 1 << 1;
+    /// Pause bit for close
+    pub const PAUSE_CLOSE: u8 = 1 << 2;
+    /// Pause bit for create_stream, claim_stream and cancel_stream
+    pub const PAUSE_STREAM: u8 = 1 << 3;
+    /// Pause bit for contribute; refunds are never paused
+    pub const PAUSE_CONTRIBUTE: u8 = 1 << 4;
+    /// Pause bit for sync_mirror
+    pub const PAUSE_MIRROR: u8 = 1 << 5;
+    /// Pause bit for migrate_out, transfer_ownership and accept_ownership
+    pub const PAUSE_MIGRATE: u8 = 1 << 6;
+    /// Action code for deposit and the other owner deposit instructions
+    pub const ACTION_DEPOSIT: u8 = 0;
+    /// Action code for withdraw, including spend_granted by a grantee and withdraw_as_delegate
+    pub const ACTION_WITHDRAW: u8 = 1;
+    /// Action code for close
+    pub const ACTION_CLOSE: u8 = 2;
+    /// Action code for claim_stream
+    pub const ACTION_CLAIM_STREAM: u8 = 3;
+    /// Action code for contribute
+    pub const ACTION_CONTRIBUTE: u8 = 4;
+    /// Action code for co-signing a withdrawal above the approval threshold
+    pub const ACTION_APPROVE: u8 = 5;
+    /// Action code for the owner-only set_* configuration instructions
+    pub const ACTION_CONFIGURE: u8 = 6;
+
+    /**
+     * @notice Reads the current unix timestamp
+     * @dev Test-mode builds return clock_override when it is set
+     * @return Result<i64> Current unix timestamp
+     */
+    pub fn now(&self) -> Result<i64> {
+        #[cfg(feature = "test-mode")]
+        if self.clock_override != 0 {
+            return Ok(self.clock_override);
+        }
+
+        Ok(Clock::get()?.unix_timestamp)
+    }
+
+    /**
+     * @notice Reads the clock timestamp for time-sensitive logic
+     * @dev When max_timestamp is set, rejects a clock earlier than the vault's creation
+     * or later than the bound, guarding against misconfigured validators and clock anomalies
+     * @return Result<i64> Current unix timestamp or ImplausibleTimestamp
+     */
+    pub fn checked_now(&self) -> Result<i64> {
+        let now = self.now()?;
+
+        if self.max_timestamp != 0 {
+            require!(
+                now >= self.created_at && now <= self.max_timestamp,
+                VaultError::ImplausibleTimestamp
+            );
+        }
+
+        Ok(now)
+    }
+
+    /**
+     * @notice Runs every check a withdrawal must pass and feeds it to the activity monitor
+     * @dev Shared by every instruction paying out an owner withdrawal, whoever signs it
+     * @param vault Vault PDA the withdrawal is taken from
+     * @param amount Amount to withdraw in lamports
+     * @param approvals Accounts offered as withdrawal approvers, None when they were already
+     * checked as the withdrawal was scheduled
+     * @return Result<()> Success or error
+     */
+    pub fn check_withdrawal(&mut self, vault: &SystemAccount, amount: u64, approvals: Option<&[AccountInfo]>) -> Result<()> {
+        self.require_not_paused(VaultState::PAUSE_WITHDRAW)?;
+        self.require_not_renounced()?;
+        require!(amount > 0, VaultError::InvalidWithdrawAmount);
+        self.require_max_withdrawal(amount)?;
+        self.require_lifetime_cap(amount)?;
+
+        let min_withdrawal = self.min_withdrawal;
+        require!(
+            min_withdrawal == 0 || amount >= min_withdrawal,
+            VaultError::WithdrawalTooSmall
+        );
+
+        if let Some(approvals) = approvals {
+            self.require_approvals(amount, approvals)?;
+        }
+
+        let vault_balance = vault.get_lamports();
+        let rent_exempt = Rent::get()?.minimum_balance(vault.to_account_info().data_len());
+
+        // Only the rent seeded at initialization is left, which is reclaimable on close
+        require!(vault_balance > rent_exempt, VaultError::OnlyRentRemains);
+        require!(!self.goal_locked(vault_balance - rent_exempt), VaultError::GoalNotReached);
+
+        if vault_balance.saturating_sub(amount) < rent_exempt {
+            let max_allowed = vault_balance - rent_exempt;
+            msg!(
+                "Withdrawal of {} lamports would break rent exemption; at most {} lamports can be withdrawn",
+                amount,
+                max_allowed
+            );
+            emit!(WithdrawRejected {
+                vault: vault.key(),
+                requested: amount,
+                max_allowed,
+            });
+
+            return err!(VaultError::InsufficientFundsAfterWithdrawal);
+        }
+
+        let now = self.checked_now()?;
+        require!(!self.is_locked(now), VaultError::VaultLocked);
+
+        let committed = self.committed_balance(now);
+        require!(
+            vault_balance.saturating_sub(amount) >= rent_exempt.saturating_add(committed),
+            VaultError::FundsCommitted
+        );
+        require!(
+            vault_balance.saturating_sub(amount) >= rent_exempt.saturating_add(committed).saturating_add(self.reserve),
+            VaultError::ReserveViolation
+        );
+
+        let floor = self.balance_floor();
+        require!(
+            vault_balance.saturating_sub(amount) >= rent_exempt.saturating_add(committed).saturating_add(floor),
+            VaultError::BelowBalanceFloor
+        );
+
+        let savings = self.locked_savings(now);
+        require!(
+            vault_balance.saturating_sub(amount)
+                >= rent_exempt.saturating_add(committed).saturating_add(floor).saturating_add(savings),
+            VaultError::SavingsLocked
+        );
+
+        let locked = self.partially_locked(now);
+        require!(
+            vault_balance.saturating_sub(amount)
+                >= rent_exempt
+                    .saturating_add(committed)
+                    .saturating_add(floor)
+                    .saturating_add(savings)
+                    .saturating_add(locked),
+            VaultError::AmountLocked
+        );
+
+        require!(amount <= self.vesting_available(now), VaultError::ExceedsVested);
+
+        require!(self.has_recent_deposit(now), VaultError::NoRecentDeposit);
+
+        self.monitor_withdrawal(vault.key(), now, amount, vault_balance);
+
+        Ok(())
+    }
+
+    /**
+     * @notice Books a completed withdrawal against the daily limit, running totals and flow history
+     * @dev Lamports withdrawn beyond what the ledger still holds were sent to the vault PDA outside
+     * the program; they are credited first so the ledger keeps covering the vault's real balance
+     * @param now Current unix timestamp
+     * @param amount Amount withdrawn in lamports
+     * @return Result<()> Success, DailyLimitExceeded, TooManyWithdrawals or MathOverflow
+     */
+    pub fn record_withdrawal(&mut self, now: i64, amount: u64) -> Result<()> {
+        self.record_daily_withdrawal(now, amount)?;
+        self.record_window_withdrawal(now)?;
+        self.book_withdrawal(now, amount)
+    }
+
+    /**
+     * @notice Books a withdrawal like record_withdrawal without enforcing the daily and window limits
+     * @dev For withdrawals that bypass the limits, such as panic_drain; the daily and window counters
+     * still advance so later withdrawals see the lamports taken
+     * @param now Current unix timestamp
+     * @param amount Amount withdrawn in lamports
+     * @return Result<()> Success or MathOverflow
+     */
+    pub fn record_withdrawal_unchecked(&mut self, now: i64, amount: u64) -> Result<()> {
+        self.settle_daily_limit(now);
+        self.count_daily_withdrawal(now, amount);
+        self.count_window_withdrawal(now);
+        self.book_withdrawal(now, amount)
+    }
+
+    /**
+     * @notice Adds a withdrawal to the running totals and flow history once its limits are settled
+     * @param now Current unix timestamp
+     * @param amount Amount withdrawn in lamports
+     * @return Result<()> Success or MathOverflow
+     */
+    fn book_withdrawal(&mut self, now: i64, amount: u64) -> Result<()> {
+        if let Some(tracked) = self.ledger_inflows().checked_sub(self.total_withdrawn) {
+            if amount > tracked {
+                msg!("Crediting {} lamports sent to the vault outside the program", amount - tracked);
+                self.credited_total = self.credited_total.checked_add(amount - tracked).ok_or(VaultError::MathOverflow)?;
+            }
+        }
+        self.total_withdrawn = match self.total_withdrawn.checked_add(amount) {
+            Some(total) => total,
+            None => {
+                msg!("Withdrawal of {} lamports would overflow total withdrawn of {} lamports", amount, self.total_withdrawn);
+                return err!(VaultError::MathOverflow);
+            }
+        };
+        if self.is_vesting(now) {
+            // Only a withdrawal bypassing the vesting check can take more than what is left vesting
+            self.vesting_withdrawn = self
+                .vesting_withdrawn
+                .checked_add(amount)
+                .ok_or(VaultError::MathOverflow)?
+                .min(self.vesting_total);
+        }
+        self.record_flow(now, 0, amount);
+        self.last_activity = now;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Emits SuspiciousActivity for a withdrawal that trips a monitoring heuristic
+     * @dev No-op without an emergency contact; also advances the rapid withdrawal window
+     * @param vault Vault PDA the withdrawal is taken from
+     * @param now Current unix timestamp
+     * @param amount Amount being withdrawn in lamports
+     * @param balance Vault balance before the withdrawal in lamports
+     */
+    pub fn monitor_withdrawal(&mut self, vault: Pubkey, now: i64, amount: u64, balance: u64) {
+        if self.emergency_contact == Pubkey::default() {
+            return;
+        }
+
+        if self.large_withdrawal_bps > 0
+            && u128::from(amount) * u128::from(BPS_DENOMINATOR)
+                > u128::from(balance) * u128::from(self.large_withdrawal_bps)
+        {
+            emit!(SuspiciousActivity {
+                vault,
+                contact: self.emergency_contact,
+                reason_code: SuspiciousActivity::REASON_LARGE_WITHDRAWAL,
+            });
+        }
+
+        if self.rapid_withdrawal_count > 0 {
+            if now.saturating_sub(self.rapid_window_start) >= self.rapid_window {
+                self.rapid_window_start = now;
+                self.rapid_window_withdrawals = 0;
+            }
+
+            self.rapid_window_withdrawals = self.rapid_window_withdrawals.saturating_add(1);
+
+            if self.rapid_window_withdrawals >= self.rapid_withdrawal_count {
+                emit!(SuspiciousActivity {
+                    vault,
+                    contact: self.emergency_contact,
+                    reason_code: SuspiciousActivity::REASON_RAPID_WITHDRAWALS,
+                });
+            }
+        }
+    }
+
+    /**
+     * @notice Hashes the owner-configurable settings in a stable order
+     * @dev Balances, counters and stream or campaign progress are excluded; new settings
+     * must be appended to the end so existing hashes only change when a value does
+     * @return [u8; 32] SHA-256 of the configuration fields
+     */
+    pub fn config_hash(&self) -> [u8; 32] {
+        let approvers: Vec<u8> = self.approvers.iter().flat_map(|approver| approver.to_bytes()).collect();
+        let beneficiaries: Vec<u8> = self
+            .beneficiaries
+            .iter()
+            .flat_map(|share| [share.beneficiary.as_ref(), &share.share_bps.to_le_bytes()].concat())
+            .collect();
+
+        hashv(&[
+            &self.min_withdrawal.to_le_bytes(),
+            self.mirror_of.as_ref(),
+            &approvers,
+            &[self.required_approvals],
+            &self.approval_threshold.to_le_bytes(),
+            self.migration_target.as_ref(),
+            &[self.paused_instructions],
+            &[u8::from(self.compact_events)],
+            &self.max_timestamp.to_le_bytes(),
+            &self.confirm_threshold.to_le_bytes(),
+            &self.confirm_window.to_le_bytes(),
+            self.emergency_contact.as_ref(),
+            &self.large_withdrawal_bps.to_le_bytes(),
+            &[self.rapid_withdrawal_count],
+            &self.rapid_window.to_le_bytes(),
+            &self.lamports_per_point.to_le_bytes(),
+            &self.reporting_threshold.to_le_bytes(),
+            &self.deposit_recency.to_le_bytes(),
+            &self.unlock_time.to_le_bytes(),
+            &self.tags.concat(),
+            &self.daily_limit.to_le_bytes(),
+            &beneficiaries,
+            self.safe_address.as_ref(),
+            &self.safe_address_delay.to_le_bytes(),
+            &self.max_balance.to_le_bytes(),
+            &self.floor_bps.to_le_bytes(),
+            &self.floor_cap.to_le_bytes(),
+            self.delegate.as_ref(),
+            &self.savings_lock_period.to_le_bytes(),
+            &self.min_deposit.to_le_bytes(),
+            self.guardian.as_ref(),
+            &self.goal_amount.to_le_bytes(),
+            &self.max_withdrawal.to_le_bytes(),
+            &self.close_delay.to_le_bytes(),
+            &self.max_withdrawals_per_window.to_le_bytes(),
+            &[u8::from(self.withdrawals_renounced)],
+            &self.reserve.to_le_bytes(),
+            &self.locked_amount.to_le_bytes(),
+            &self.partial_unlock_time.to_le_bytes(),
+            &[u8::from(self.auto_close)],
+            &self.expires_at.to_le_bytes(),
+            &self.lifetime_withdraw_cap.to_le_bytes(),
+            &[u8::from(self.admin_managed)],
+            &self.pending_daily_limit.to_le_bytes(),
+            &self.daily_limit_change_at.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+
+    /**
+     * @notice Zero-pads a tag into its fixed-size stored form
+     * @param tag Tag of 1 to TAG_LEN bytes
+     * @return Result<[u8; TAG_LEN]> Encoded tag or InvalidTag
+     */
+    pub fn encode_tag(tag: &str) -> Result<[u8; TAG_LEN]> {
+        let bytes = tag.as_bytes();
+        require!(!bytes.is_empty() && bytes.len() <= TAG_LEN, VaultError::InvalidTag);
+        require!(!bytes.contains(&0), VaultError::InvalidTag);
+
+        let mut encoded = [0; TAG_LEN];
+        encoded[..bytes.len()].copy_from_slice(bytes);
+        Ok(encoded)
+    }
+
+    /**
+     * @notice Fails until the vault has an expiry and it has passed
+     * @param now Current unix timestamp
+     * @return Result<()> Success or NotExpired
+     */
+    pub fn require_expired(&self, now: i64) -> Result<()> {
+        require!(self.expires_at != 0 && now >= self.expires_at, VaultError::NotExpired);
+        Ok(())
+    }
+
+    /**
+     * @notice Whether the time lock still blocks funds from leaving the vault
+     * @param now Current unix timestamp
+     * @return bool True before unlock_time
+     */
+    pub fn is_locked(&self, now: i64) -> bool {
+        now < self.unlock_time
+    }
+
+    /**
+     * @notice Balance withdrawals must keep on top of rent and committed funds
+     * @dev The fixed reserve and rent buffer plus a share that grows with lifetime deposits,
+     * so consistent savers keep more in reserve
+     * @return u64 Floor in lamports
+     */
+    pub fn balance_floor(&self) -> u64 {
+        let floor = (u128::from(self.total_deposited) * u128::from(self.floor_bps) / u128::from(BPS_DENOMINATOR)) as u64;
+        let floor = if self.floor_cap == 0 {
+            floor
+        } else {
+            floor.min(self.floor_cap)
+        };
+
+        floor.saturating_add(self.reserve).saturating_add(self.rent_buffer)
+    }
+
+    /**
+     * @notice Whether the guardian has started a recovery that is neither finalized nor cancelled
+     * @return bool True while a recovery is pending
+     */
+    pub fn has_pending_recovery(&self) -> bool {
+        self.recovery_authority != Pubkey::default()
+    }
+
+    /**
+     * @notice Fails once the owner has renounced withdrawals
+     * @return Result<()> Success or WithdrawalsRenounced
+     */
+    pub fn require_not_renounced(&self) -> Result<()> {
+        require!(!self.withdrawals_renounced, VaultError::WithdrawalsRenounced);
+        Ok(())
+    }
+
+    /**
+     * @notice Returns whether an unreached savings goal still keeps funds in the vault
+     * @param usable_balance Vault balance above rent in lamports
+     */
+    pub fn goal_locked(&self, usable_balance: u64) -> bool {
+        self.goal_amount > 0 && !self.goal_reached && usable_balance < self.goal_amount
+    }
+
+    /**
+     * @notice Drops any pending recovery
+     */
+    pub fn clear_recovery(&mut self) {
+        self.recovery_authority = Pubkey::default();
+        self.recovery_initiated_at = 0;
+    }
+
+    /**
+     * @notice Savings tranche that cannot leave the vault yet
+     * @param now Current unix timestamp
+     * @return u64 Locked savings in lamports (0 once the tranche has unlocked)
+     */
+    pub fn locked_savings(&self, now: i64) -> u64 {
+        if now < self.savings_unlock_time {
+            self.savings_tranche
+        } else {
+            0
+        }
+    }
+
+    /**
+     * @notice Portion of the balance set_partial_lock still holds back
+     * @param now Current unix timestamp
+     * @return u64 Locked amount in lamports (0 once partial_unlock_time has passed)
+     */
+    pub fn partially_locked(&self, now: i64) -> u64 {
+        if now < self.partial_unlock_time {
+            self.locked_amount
+        } else {
+            0
+        }
+    }
+
+    /**
+     * @notice Builds the state of a new vault with every setting at its default
+     * @param owner Wallet the vault PDAs are seeded by and its authority
+     * @param index Index of the vault among the owner's vaults
+     * @param external_ref Opaque off-chain reference ID stored with the vault
+     * @param label Display name for the vault, all zeros when unlabeled
+     * @param state_bump Bump seed of the vault state PDA
+     * @param vault_bump Bump seed of the vault PDA
+     * @return Result<VaultState> New vault state or error
+     */
+    pub fn new(
+        owner: Pubkey,
+        index: u64,
+        external_ref: u64,
+        label: [u8; LABEL_LEN],
+        state_bump: u8,
+        vault_bump: u8,
+    ) -> Result<Self> {
+        // A new vault has no clock override yet, so this is the timestamp now() would read
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        Ok(VaultState {
+            state_bump,
+            vault_bump,
+            min_withdrawal: 0,
+            external_ref,
+            mirror_of: Pubkey::default(),
+            approvers: [Pubkey::default(); VaultState::MAX_APPROVERS],
+            required_approvals: 0,
+            approval_threshold: 0,
+            stream_recipient: Pubkey::default(),
+            stream_total: 0,
+            stream_rate: 0,
+            stream_start: 0,
+            stream_claimed: 0,
+            funding_goal: 0,
+            funding_deadline: 0,
+            funding_raised: 0,
+            created_slot: clock.slot,
+            flow_buckets: [FlowBucket::default(); VaultState::FLOW_DAYS],
+            migration_target: Pubkey::default(),
+            paused_instructions: 0,
+            compact_events: false,
+            created_at: now,
+            max_timestamp: 0,
+            confirm_threshold: 0,
+            confirm_window: 0,
+            proposed_deposit: 0,
+            proposed_at: 0,
+            emergency_contact: Pubkey::default(),
+            large_withdrawal_bps: 0,
+            rapid_withdrawal_count: 0,
+            rapid_window: 0,
+            rapid_window_start: 0,
+            rapid_window_withdrawals: 0,
+            reward_points: 0,
+            lamports_per_point: 0,
+            total_deposited: 0,
+            reporting_threshold: 0,
+            threshold_reported: false,
+            last_deposit_time: 0,
+            deposit_recency: 0,
+            unlock_time: 0,
+            tags: [[0; TAG_LEN]; MAX_TAGS],
+            total_withdrawn: 0,
+            mint: Pubkey::default(),
+            daily_limit: 0,
+            withdrawn_today: 0,
+            last_withdraw_day: 0,
+            beneficiaries: [BeneficiaryShare::default(); VaultState::MAX_BENEFICIARIES],
+            authority: owner,
+            safe_address: Pubkey::default(),
+            safe_address_set_at: 0,
+            safe_address_delay: 0,
+            max_balance: 0,
+            is_initialized: true,
+            floor_bps: 0,
+            floor_cap: 0,
+            last_activity: now,
+            delegate: Pubkey::default(),
+            savings_lock_period: 0,
+            savings_tranche: 0,
+            savings_unlock_time: 0,
+            min_deposit: MIN_DEPOSIT_AMOUNT,
+            label,
+            index,
+            guardian: Pubkey::default(),
+            recovery_authority: Pubkey::default(),
+            recovery_initiated_at: 0,
+            goal_amount: 0,
+            goal_reached: false,
+            vesting_start: 0,
+            vesting_end: 0,
+            vesting_total: 0,
+            vesting_withdrawn: 0,
+            max_withdrawal: MAX_WITHDRAWAL_AMOUNT,
+            close_delay: CLOSE_DELAY,
+            close_requested_at: 0,
+            max_withdrawals_per_window: 0,
+            withdraw_count_window: 0,
+            window_start: 0,
+            last_accrual: 0,
+            withdrawals_renounced: false,
+            reserve: 0,
+            locked_amount: 0,
+            partial_unlock_time: 0,
+            auto_close: false,
+            scheduled_withdrawal: ScheduledWithdrawal::default(),
+            expires_at: 0,
+            pending_authority: Pubkey::default(),
+            lifetime_withdraw_cap: 0,
+            rent_buffer: 0,
+            matched_total: 0,
+            credited_total: 0,
+            admin_managed: false,
+            pending_daily_limit: 0,
+            daily_limit_change_at: 0,
+            version: VaultState::VERSION,
+            #[cfg(feature = "test-mode")]
+            clock_override: 0,
+        })
+    }
+
+    /**
+     * @notice Fills in the defaults of fields an older layout lacked and stamps the current version
+     * @dev Those fields were zero-filled by the resize, so only settings whose zero value is
+     * invalid are replaced; close_delay stays zero, keeping the single-step close older vaults had
+     * @param owner Owner of the vault, also the authority of vaults predating the field
+     */
+    pub fn upgrade(&mut self, owner: Pubkey) {
+        if self.authority == Pubkey::default() {
+            self.authority = owner;
+        }
+        if self.min_deposit == 0 {
+            self.min_deposit = MIN_DEPOSIT_AMOUNT;
+        }
+        if self.max_withdrawal == 0 {
+            self.max_withdrawal = MAX_WITHDRAWAL_AMOUNT;
+        }
+        self.is_initialized = true;
+        self.version = Self::VERSION;
+    }
+
+    /**
+     * @notice Sets the unlock time, refusing to shorten an active lock
+     * @dev An unlock time in the past counts as shorter than any minimum
+     * @param now Current unix timestamp
+     * @param unlock_time Unix timestamp from which funds can leave the vault again
+     * @param min_duration Shortest lock accepted, in seconds from now
+     * @return Result<()> Success, VaultLocked or LockTooShort
+     */
+    pub fn extend_lock(&mut self, now: i64, unlock_time: i64, min_duration: i64) -> Result<()> {
+        require!(
+            !self.is_locked(now) || unlock_time >= self.unlock_time,
+            VaultError::VaultLocked
+        );
+        require!(unlock_time >= now.saturating_add(min_duration), VaultError::LockTooShort);
+        self.unlock_time = unlock_time;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Checks the close delay for a close or confirm_close
+     * @dev A single-step close needs a zero delay; a confirmed close needs a pending request
+     * at least close_delay seconds old
+     * @param now Current unix timestamp
+     * @param confirmed Whether the close confirms a pending close request
+     * @return Result<()> Success, NoCloseRequested or CloseDelayActive
+     */
+    pub fn require_close_allowed(&self, now: i64, confirmed: bool) -> Result<()> {
+        if confirmed {
+            require!(self.close_requested_at != 0, VaultError::NoCloseRequested);
+            require!(
+                now >= self.close_requested_at.saturating_add(self.close_delay),
+                VaultError::CloseDelayActive
+            );
+        } else {
+            require!(self.close_delay == 0, VaultError::CloseDelayActive);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Returns whether a vesting window still restricts withdrawals
+     * @param now Current unix timestamp
+     */
+    pub fn is_vesting(&self, now: i64) -> bool {
+        now < self.vesting_end
+    }
+
+    /**
+     * @notice Share of vesting_total released by now
+     * @dev Nothing is vested before vesting_start and everything from vesting_end on; in between
+     * the release is linear
+     * @param now Current unix timestamp
+     * @return u64 Vested amount in lamports
+     */
+    pub fn vested_amount(&self, now: i64) -> u64 {
+        if now <= self.vesting_start {
+            0
+        } else if now >= self.vesting_end {
+            self.vesting_total
+        } else {
+            let elapsed = (now - self.vesting_start) as u128;
+            let duration = (self.vesting_end - self.vesting_start) as u128;
+            (u128::from(self.vesting_total) * elapsed / duration) as u64
+        }
+    }
+
+    /**
+     * @notice Amount the vesting window still lets withdrawals take
+     * @param now Current unix timestamp
+     * @return u64 Vested minus already withdrawn in lamports, or u64::MAX outside a vesting window
+     */
+    pub fn vesting_available(&self, now: i64) -> u64 {
+        if self.is_vesting(now) {
+            self.vested_amount(now).saturating_sub(self.vesting_withdrawn)
+        } else {
+            u64::MAX
+        }
+    }
+
+    /**
+     * @notice Amount the lifetime withdrawal cap still lets withdrawals take
+     * @return u64 Cap minus total withdrawn in lamports, or u64::MAX without a cap
+     */
+    pub fn lifetime_available(&self) -> u64 {
+        if self.lifetime_withdraw_cap == 0 {
+            u64::MAX
+        } else {
+            self.lifetime_withdraw_cap.saturating_sub(self.total_withdrawn)
+        }
+    }
+
+    /**
+     * @notice Fails if the withdrawal would take total_withdrawn past the lifetime cap
+     * @param amount Amount being withdrawn in lamports
+     * @return Result<()> Success or LifetimeCapExceeded
+     */
+    pub fn require_lifetime_cap(&self, amount: u64) -> Result<()> {
+        let available = self.lifetime_available();
+        if amount > available {
+            msg!(
+                "Withdrawal of {} lamports exceeds the {} lamports left under the lifetime cap of {} lamports",
+                amount,
+                available,
+                self.lifetime_withdraw_cap
+            );
+            return err!(VaultError::LifetimeCapExceeded);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Fails if a token vault's token account is missing or still holds tokens
+     * @dev Closing drains lamports only, so tokens left behind would be stranded in an
+     * account whose authority no longer has a state to sign for it
+     * @param vault_token_account The vault's associated token account, if supplied
+     * @return Result<()> Success or TokenBalanceRemaining
+     */
+    pub fn require_tokens_withdrawn(&self, vault_token_account: Option<&Account<TokenAccount>>) -> Result<()> {
+        if self.mint == Pubkey::default() {
+            return Ok(());
+        }
+
+        let token_account = vault_token_account.ok_or(VaultError::TokenBalanceRemaining)?;
+        require!(token_account.amount == 0, VaultError::TokenBalanceRemaining);
+
+        Ok(())
+    }
+
+    /**
+     * @notice Lamports booked into the vault: deposits, matches and credits
+     * @return u64 Sum of total_deposited, matched_total and credited_total, saturating
+     */
+    pub fn ledger_inflows(&self) -> u64 {
+        self.total_deposited
+            .saturating_add(self.matched_total)
+            .saturating_add(self.credited_total)
+    }
+
+    /**
+     * @notice Fails if the state contradicts itself or the vault balance it describes
+     * @dev Run as the last step of every instruction moving lamports in or out of the vault. An empty vault, as left
+     * by a close, is exempt from the rent check. Lamports sent to the vault PDA directly are credited
+     * by record_withdrawal as they are withdrawn, so only a pre-existing ledger deficit trips the check
+     * @param vault Vault PDA the state describes
+     * @return Result<()> Success or InvariantViolation
+     */
+    pub fn assert_invariants(&self, vault: &SystemAccount) -> Result<()> {
+        let vault_balance = vault.get_lamports();
+        let rent_exempt = Rent::get()?.minimum_balance(vault.to_account_info().data_len());
+        if vault_balance > 0 && vault_balance < rent_exempt {
+            msg!("Vault balance of {} lamports is below its rent-exempt minimum of {} lamports", vault_balance, rent_exempt);
+            return err!(VaultError::InvariantViolation);
+        }
+
+        let credited = self.ledger_inflows();
+        if self.total_withdrawn > credited {
+            msg!("Total withdrawn of {} lamports exceeds the {} lamports credited to the vault", self.total_withdrawn, credited);
+            return err!(VaultError::InvariantViolation);
+        }
+
+        if !self.is_initialized
+            || (self.pending_authority != Pubkey::default() && self.pending_authority == self.authority)
+            || self.vesting_withdrawn > self.vesting_total
+        {
+            msg!("Vault state flags are inconsistent");
+            return err!(VaultError::InvariantViolation);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Sets the partial lock, refusing to reduce or shorten an active one
+     * @param now Current unix timestamp
+     * @param amount Lamports kept in the vault until unlock_time
+     * @param unlock_time Unix timestamp from which the locked portion can be withdrawn
+     * @return Result<()> Success or AmountLocked
+     */
+    pub fn extend_partial_lock(&mut self, now: i64, amount: u64, unlock_time: i64) -> Result<()> {
+        require!(
+            self.partially_locked(now) == 0 || (amount >= self.locked_amount && unlock_time >= self.partial_unlock_time),
+            VaultError::AmountLocked
+        );
+        self.locked_amount = amount;
+        self.partial_unlock_time = unlock_time;
+
+        Ok(())
+    }
+
+    /**
+     * @notice Adds diverted savings to the tranche and restarts its lock
+     * @dev An unlocked tranche is spendable again, so a new diversion starts a fresh one
+     * @param now Current unix timestamp
+     * @param amount Savings diverted in lamports
+     * @return Result<()> Success or MathOverflow
+     */
+    pub fn lock_savings(&mut self, now: i64, amount: u64) -> Result<()> {
+        self.savings_tranche = self
+            .locked_savings(now)
+            .checked_add(amount)
+            .ok_or(VaultError::MathOverflow)?;
+        self.savings_unlock_time = now.saturating_add(self.savings_lock_period);
+
+        Ok(())
+    }
+
+    /**
+     * @notice Whether the deposit recency policy lets a withdrawal through
+     * @param now Current unix timestamp
+     * @return bool True when the policy is disabled or the last deposit is recent enough
+     */
+    pub fn has_recent_deposit(&self, now: i64) -> bool {
+        self.deposit_recency == 0 || now.saturating_sub(self.last_deposit_time) <= self.deposit_recency
+    }
+
+    /**
+     * @notice Whether close splits the balance among beneficiaries instead of paying the owner
+     * @return bool True when at least one beneficiary is configured
+     */
+    pub fn has_beneficiaries(&self) -> bool {
+        self.beneficiaries.iter().any(|share| share.beneficiary != Pubkey::default())
+    }
+
+    /**
+     * @notice Amount still withdrawable today under the daily limit
+     * @param now Current unix timestamp
+     * @return u64 Remaining allowance in lamports (u64::MAX when unlimited)
+     */
+    pub fn daily_remaining(&self, now: i64) -> u64 {
+        let daily_limit = self.current_daily_limit(now);
+        if daily_limit == 0 {
+            return u64::MAX;
+        }
+
+        let withdrawn = if now.div_euclid(SECONDS_PER_DAY) == self.last_withdraw_day {
+            self.withdrawn_today
+        } else {
+            0
+        };
+        daily_limit.saturating_sub(withdrawn)
+    }
+
+    /**
+     * @notice Daily limit in force, counting a pending change whose delay has passed
+     * @param now Current unix timestamp
+     * @return u64 Daily limit in lamports (0 when unlimited)
+     */
+    pub fn current_daily_limit(&self, now: i64) -> u64 {
+        if self.daily_limit_change_at != 0 && now >= self.daily_limit_change_at {
+            self.pending_daily_limit
+        } else {
+            self.daily_limit
+        }
+    }
+
+    /**
+     * @notice Applies a pending daily limit change whose delay has passed
+     * @param now Current unix timestamp
+     */
+    pub fn settle_daily_limit(&mut self, now: i64) {
+        if self.daily_limit_change_at != 0 && now >= self.daily_limit_change_at {
+            self.daily_limit = self.pending_daily_limit;
+            self.pending_daily_limit = 0;
+            self.daily_limit_change_at = 0;
+        }
+    }
+
+    /**
+     * @notice Adds a withdrawal to today's total, starting a new total on a new day
+     * @param now Current unix timestamp
+     * @param amount Amount being withdrawn in lamports
+     * @return Result<()> Success or DailyLimitExceeded
+     */
+    pub fn record_daily_withdrawal(&mut self, now: i64, amount: u64) -> Result<()> {
+        self.settle_daily_limit(now);
+        require!(amount <= self.daily_remaining(now), VaultError::DailyLimitExceeded);
+        self.count_daily_withdrawal(now, amount);
+
+        Ok(())
+    }
+
+    /**
+     * @notice Adds a withdrawal to today's total without checking the daily limit
+     * @param now Current unix timestamp
+     * @param amount Amount withdrawn in lamports
+     */
+    fn count_daily_withdrawal(&mut self, now: i64, amount: u64) {
+        let today = now.div_euclid(SECONDS_PER_DAY);
+        if today != self.last_withdraw_day {
+            self.last_withdraw_day = today;
+            self.withdrawn_today = 0;
+        }
+        self.withdrawn_today = self.withdrawn_today.saturating_add(amount);
+    }
+
+    /**
+     * @notice Whether another withdrawal fits in the current withdrawal window
+     * @param now Current unix timestamp
+     * @return bool True when the count limit is disabled, expired or not yet reached
+     */
+    pub fn within_withdrawal_window(&self, now: i64) -> bool {
+        self.max_withdrawals_per_window == 0
+            || now >= self.window_start.saturating_add(WITHDRAWAL_WINDOW)
+            || self.withdraw_count_window < self.max_withdrawals_per_window
+    }
+
+    /**
+     * @notice Counts a withdrawal against the current window, starting a new window once it expires
+     * @param now Current unix timestamp
+     * @return Result<()> Success or TooManyWithdrawals
+     */
+    pub fn record_window_withdrawal(&mut self, now: i64) -> Result<()> {
+        require!(self.within_withdrawal_window(now), VaultError::TooManyWithdrawals);
+        self.count_window_withdrawal(now);
+
+        Ok(())
+    }
+
+    /**
+     * @notice Counts a withdrawal against the current window without checking the count limit
+     * @param now Current unix timestamp
+     */
+    fn count_window_withdrawal(&mut self, now: i64) {
+        if now >= self.window_start.saturating_add(WITHDRAWAL_WINDOW) {
+            self.window_start = now;
+            self.withdraw_count_window = 0;
+        }
+        self.withdraw_count_window = self.withdraw_count_window.saturating_add(1);
+    }
+
+    /**
+     * @notice Fails if a deposit is below the vault's minimum, logging the bound it missed
+     * @param amount Amount being deposited in lamports
+     * @return Result<()> Success or InsufficientDepositAmount
+     */
+    pub fn require_min_deposit(&self, amount: u64) -> Result<()> {
+        if amount < self.min_deposit {
+            msg!("Deposit of {} lamports is below the minimum deposit of {} lamports", amount, self.min_deposit);
+            return err!(VaultError::InsufficientDepositAmount);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Adds a deposit to the lifetime total, logging the total it would overflow
+     * @param amount Amount being deposited in lamports
+     * @return Result<()> Success or MathOverflow
+     */
+    pub fn record_deposit(&mut self, amount: u64) -> Result<()> {
+        self.total_deposited = match self.total_deposited.checked_add(amount) {
+            Some(total) => total,
+            None => {
+                msg!("Deposit of {} lamports would overflow total deposited of {} lamports", amount, self.total_deposited);
+                return err!(VaultError::MathOverflow);
+            }
+        };
+
+        Ok(())
+    }
+
+    /**
+     * @notice Fails if a withdrawal exceeds the vault's per-transaction maximum, logging the bound it missed
+     * @param amount Amount being withdrawn in lamports
+     * @return Result<()> Success or ExceedsMaxWithdrawal
+     */
+    pub fn require_max_withdrawal(&self, amount: u64) -> Result<()> {
+        if amount > self.max_withdrawal {
+            msg!("Withdrawal of {} lamports exceeds the maximum withdrawal of {} lamports", amount, self.max_withdrawal);
+            return err!(VaultError::ExceedsMaxWithdrawal);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * @notice Fails if a direct deposit of this size must go through propose_deposit
+     * @param amount Amount being deposited in lamports
+     * @return Result<()> Success or DepositRequiresConfirmation
+     */
+    pub fn require_direct_deposit(&self, amount: u64) -> Result<()> {
+        require!(
+            self.confirm_threshold == 0 || amount <= self.confirm_threshold,
+            VaultError::DepositRequiresConfirmation
+        );
+        Ok(())
+    }
+
+    /**
+     * @notice Fails if the owner has paused the instruction behind the given bit
+     * @param flag One of the PAUSE_* bits
+     * @return Result<()> Success or InstructionPaused
+     */
+    pub fn require_not_paused(&self, flag: u8) -> Result<()> {
+        require!(self.paused_instructions & flag == 0, VaultError::InstructionPaused);
+        Ok(())
+    }
+
+    /**
+     * @notice Adds lamport movements to the bucket for the current day and restarts the reward clock
+     * @dev Reuses the oldest bucket when the day rotates into its slot. Accrue pays on the balance at
+     * call time, so any balance change restarts an opted-in vault's clock; rewards earned before it
+     * must be accrued first, or they are forfeited
+     * @param now Current unix timestamp
+     * @param inflow Lamports that entered the vault
+     * @param outflow Lamports that left the vault
+     */
+    pub fn record_flow(&mut self, now: i64, inflow: u64, outflow: u64) {
+        if self.last_accrual != 0 {
+            self.last_accrual = now;
+        }
+
+        let day = now.div_euclid(SECONDS_PER_DAY);
+        let bucket = &mut self.flow_buckets[Self::flow_index(day)];
+
+        if bucket.day != day {
+            *bucket = FlowBucket { day, inflow: 0, outflow: 0 };
+        }
+
+        bucket.inflow = bucket.inflow.saturating_add(inflow);
+        bucket.outflow = bucket.outflow.saturating_add(outflow);
+    }
+
+    /**
+     * @notice Returns the flow buckets for the last FLOW_DAYS days
+     * @dev Buckets left over from earlier rotations are reported as empty
+     * @param now Current unix timestamp
+     * @return Vec<FlowBucket> Buckets ordered oldest to newest
+     */
+    pub fn recent_flows(&self, now: i64) -> Vec<FlowBucket> {
+        let today = now.div_euclid(SECONDS_PER_DAY);
+        let first_day = today - (Self::FLOW_DAYS as i64 - 1);
+
+        (first_day..=today)
+            .map(|day| {
+                let bucket = self.flow_buckets[Self::flow_index(day)];
+                if bucket.day == day {
+                    bucket
+                } else {
+                    FlowBucket { day, inflow: 0, outflow: 0 }
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the ring buffer slot for a day
+    fn flow_index(day: i64) -> usize {
+        day.rem_euclid(Self::FLOW_DAYS as i64) as usize
+    }
+
+    /**
+     * @notice Returns the funds held in the vault on behalf of others
+     * @dev Plain withdrawals must leave these untouched on top of the rent-exempt minimum
+     * @param now Current unix timestamp
+     * @return u64 Committed amount in lamports
+     */
+    pub fn committed_balance(&self, now: i64) -> u64 {
+        let streamed = self.stream_total.saturating_sub(self.stream_claimed);
+
+        // Contributions stay refundable until the campaign succeeds
+        let contributed = if self.funding_succeeded(now) { 0 } else { self.funding_raised };
+
+        streamed.saturating_add(contributed)
+    }
+
+    /// Returns whether the funding deadline has passed with the goal met
+    pub fn funding_succeeded(&self, now: i64) -> bool {
+        now >= self.funding_deadline && self.funding_raised >= self.funding_goal
+    }
+
+    /// Returns whether a funding campaign is running or still holds refundable contributions
+    pub fn has_active_campaign(&self, now: i64) -> bool {
+        self.funding_goal > 0 && (now < self.funding_deadline || (!self.funding_succeeded(now) && self.funding_raised > 0))
+    }
+
+    /// Returns whether a stream is currently active
+    pub fn has_active_stream(&self) -> bool {
+        self.stream_recipient != Pubkey::default()
+    }
+
+    /**
+     * @notice Computes the stream amount vested at a point in time
+     * @dev Vesting is rate_per_sec * elapsed, capped at the stream total
+     * @param now Current unix timestamp
+     * @return u64 Vested amount in lamports
+     */
+    pub fn stream_vested(&self, now: i64) -> u64 {
+        if !self.has_active_stream() {
+            return 0;
+        }
+
+        let elapsed = u64::try_from(now.saturating_sub(self.stream_start)).unwrap_or(0);
+        self.stream_rate.saturating_mul(elapsed).min(self.stream_total)
+    }
+
+    /// Returns the vested stream amount not yet claimed by the recipient
+    pub fn stream_claimable(&self, now: i64) -> u64 {
+        self.stream_vested(now).saturating_sub(self.stream_claimed)
+    }
+
+    /// Resets the stream fields once a stream has ended
+    pub fn clear_stream(&mut self) {
+        self.stream_recipient = Pubkey::default();
+        self.stream_total = 0;
+        self.stream_rate = 0;
+        self.stream_start = 0;
+        self.stream_claimed = 0;
+    }
+
+    /**
+     * @notice Verifies that an outflow has collected the approvals it needs
+     * @dev Counts each configured approver at most once, so duplicate accounts can't inflate the tally
+     * @param amount Amount leaving the vault in lamports
+     * @param signers Accounts offered as approvers (typically remaining_accounts)
+     * @return Result<()> Success or error
+     */
+    pub fn require_approvals(&self, amount: u64, signers: &[AccountInfo]) -> Result<()> {
+        if self.required_approvals == 0 || amount <= self.approval_threshold {
+            return Ok(());
+        }
+
+        let approvals = self
+            .approvers
+            .iter()
+            .filter(|approver| **approver != Pubkey::default())
+            .filter(|approver| signers.iter().any(|a| a.is_signer && a.key == *approver))
+            .count();
+
+        require!(
+            approvals >= usize::from(self.required_approvals),
+            VaultError::InsufficientApprovals
+        );
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Inflow and outflow totals for a single day
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FlowBucket {
+    /// Day number (unix timestamp / SECONDS_PER_DAY)
+    pub day: i64,
+    /// Lamports that entered the vault during the day
+    pub inflow: u64,
+    /// Lamports that left the vault during the day
+    pub outflow: u64,
+}
+
+/**
+ * @notice A withdrawal queued for execution by anyone after a given time
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScheduledWithdrawal {
+    /// Amount to withdraw in lamports (0 when nothing is scheduled)
+    pub amount: u64,
+    /// Account receiving the funds
+    pub recipient: Pubkey,
+    /// Unix timestamp from which the withdrawal can be executed
+    pub execute_after: i64,
+}
+
+impl ScheduledWithdrawal {
+    /**
+     * @notice Whether a withdrawal is waiting to be executed
+     * @return bool True when an amount is scheduled
+     */
+    pub fn is_pending(&self) -> bool {
+        self.amount > 0
+    }
+}
+
+/**
+ * @notice A beneficiary's share of the vault balance paid out on close
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BeneficiaryShare {
+    /// Account receiving the share
+    pub beneficiary: Pubkey,
+    /// Share of the balance in basis points
+    pub share_bps: u16,
+}
+
+/**
+ * @notice Per-contributor record of funds given to a campaign
+ * @dev PDA seeded by the vault state and the contributor
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct Contribution {
+    /// Total contributed by this contributor in lamports
+    pub amount: u64,
+    /// Bump seed for the contribution PDA
+    pub bump: u8,
+}
+
+impl Contribution {
+    /// Seed constant for contribution PDA
+    pub const SEED: &'static [u8] = b"contribution";
+}
+
+/**
+ * @notice A single recorded access attempt
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Debug, PartialEq, Eq)]
+pub struct AccessEntry {
+    /// Key that accessed the vault
+    pub accessor: Pubkey,
+    /// Unix timestamp of the access
+    pub timestamp: i64,
+    /// Stated purpose of the access
+    #[max_len(MAX_PURPOSE_LEN)]
+    pub purpose: String,
+}
+
+/**
+ * @notice Withdrawal recipients approved by a vault owner
+ * @dev PDA seeded by the owner, shared by all of their vaults; allocated for
+ * MAX_WHITELIST_RECIPIENTS entries up front
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct Whitelist {
+    /// Owner whose vaults may withdraw to these recipients
+    pub owner: Pubkey,
+    /// Approved recipients, in no particular order
+    #[max_len(MAX_WHITELIST_RECIPIENTS)]
+    pub recipients: Vec<Pubkey>,
+    /// Bump seed for the whitelist PDA
+    pub bump: u8,
+}
+
+impl Whitelist {
+    /// Seed constant for whitelist PDA
+    pub const SEED: &'static [u8] = b"whitelist";
+}
+
+/**
+ * @notice Ring buffer of access attempts for a vault
+ * @dev PDA seeded by the vault state; holds at most MAX_ACCESS_ENTRIES entries
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct AccessLog {
+    /// Vault state this log belongs to
+    pub vault_state: Pubkey,
+    /// Recorded entries; once full, next_index points at the oldest entry
+    #[max_len(MAX_ACCESS_ENTRIES)]
+    pub entries: Vec<AccessEntry>,
+    /// Slot the next entry is written to once the log is full
+    pub next_index: u8,
+    /// Number of entries ever recorded, including overwritten ones
+    pub total_entries: u64,
+    /// Bump seed for the access log PDA
+    pub bump: u8,
+}
+
+impl AccessLog {
+    /// Seed constant for access log PDA
+    pub const SEED: &'static [u8] = b"access_log";
+
+    /**
+     * @notice Appends an entry, overwriting the oldest one when the log is full
+     * @param entry Entry to record
+     */
+    pub fn record(&mut self, entry: AccessEntry) {
+        if self.entries.len() < MAX_ACCESS_ENTRIES {
+            self.entries.push(entry);
+        } else {
+            self.entries[usize::from(self.next_index)] = entry;
+            self.next_index = ((usize::from(self.next_index) + 1) % MAX_ACCESS_ENTRIES) as u8;
+        }
+
+        self.total_entries = self.total_entries.saturating_add(1);
+    }
+
+    /**
+     * @notice Collects up to count entries starting at the start-th oldest stored entry
+     * @dev next_index is 0 until the log is full, so it always points at the oldest entry
+     * @param start Chronological index of the first entry
+     * @param count Maximum number of entries, at most MAX_LOG_PAGE
+     * @return Result<LogPage> Requested page or InvalidLogPage
+     */
+    pub fn page(&self, start: u8, count: u8) -> Result<LogPage> {
+        let stored = self.entries.len();
+        require!(count > 0 && count <= MAX_LOG_PAGE, VaultError::InvalidLogPage);
+        require!(usize::from(start) < stored, VaultError::InvalidLogPage);
+
+        let end = stored.min(usize::from(start) + usize::from(count));
+        let entries = (usize::from(start)..end)
+            .map(|i| self.entries[(usize::from(self.next_index) + i) % stored].clone())
+            .collect();
+
+        Ok(LogPage {
+            total_entries: self.total_entries,
+            stored: stored as u8,
+            entries,
+        })
+    }
+}
+
+/**
+ * @notice Scoped withdrawal authority delegated by a vault owner
+ * @dev PDA seeded by the vault state and the grantee
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct SpendingGrant {
+    /// Account allowed to spend from the vault
+    pub grantee: Pubkey,
+    /// Maximum cumulative amount the grantee may withdraw in lamports
+    pub ceiling: u64,
+    /// Amount already withdrawn under this grant in lamports
+    pub spent: u64,
+    /// Unix timestamp after which the grant can no longer be used
+    pub expires_at: i64,
+    /// Bump seed for the grant PDA
+    pub bump: u8,
+}
+
+impl SpendingGrant {
+    /// Seed constant for spending grant PDA
+    pub const SEED: &'static [u8] = b"grant";
+}
+
+/**
+ * @notice Program-wide settings shared by every vault
+ * @dev Singleton PDA seeded by a constant
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    /// Key allowed to pause and unpause the program
+    pub admin: Pubkey,
+    /// Whether deposits, withdrawals and closes are frozen program-wide
+    pub paused: bool,
+    /// Bump seed for the config PDA
+    pub bump: u8,
+    /// System account receiving withdrawal fees
+    pub treasury: Pubkey,
+    /// Fee charged on owner withdrawals in basis points (0 disables fees)
+    pub withdraw_fee_bps: u16,
+    /// Whether owner withdrawals must be top-level instructions rather than CPIs
+    pub cpi_guard: bool,
+    /// Whether vault initialization, deposit, withdrawal and close events are emitted
+    pub emit_events: bool,
+    /// Whether deposit, withdraw and close log their remaining compute units
+    pub log_compute: bool,
+    /// Shortest lock set_lock and deposit_and_lock accept, in seconds
+    pub min_lock_duration: i64,
+}
+
+impl Config {
+    /// Seed constant for config PDA
+    pub const SEED: &'static [u8] = b"config";
+
+    /**
+     * @notice Logs the remaining compute units when log_compute is on
+     * @param checkpoint Point in the instruction being measured, e.g. "deposit start"
+     */
+    pub fn log_compute_units(&self, checkpoint: &str) {
+        if self.log_compute {
+            msg!("Compute units at {}", checkpoint);
+            sol_log_compute_units();
+        }
+    }
+
+    /**
+     * @notice Splits a withdrawal into the amount paid out and the withdrawal fee
+     * @dev Shared by every instruction paying out an owner withdrawal, so no payout path skips the fee
+     * @param amount Amount to withdraw in lamports, including the fee
+     * @return Result<(u64, u64)> Net amount and fee in lamports, or MathOverflow
+     */
+    pub fn withdrawal_split(&self, amount: u64) -> Result<(u64, u64)> {
+        let fee = (u128::from(amount) * u128::from(self.withdraw_fee_bps) / u128::from(BPS_DENOMINATOR)) as u64;
+        let net_amount = amount.checked_sub(fee).ok_or(VaultError::MathOverflow)?;
+
+        Ok((net_amount, fee))
+    }
+
+    /**
+     * @notice Fails when the CPI guard is on and this instruction was invoked by another program
+     * @dev The instructions sysvar only lists top-level instructions, so a CPI shows up as the
+     * current top-level instruction belonging to a different program
+     * @param instructions Instructions sysvar account
+     * @return Result<()> Success or CpiNotAllowed
+     */
+    pub fn require_top_level(&self, instructions: &AccountInfo) -> Result<()> {
+        if !self.cpi_guard {
+            return Ok(());
+        }
+
+        let current_index = load_current_index_checked(instructions)?;
+        let current = load_instruction_at_checked(usize::from(current_index), instructions)?;
+        require_keys_eq!(current.program_id, crate::ID, VaultError::CpiNotAllowed);
+
+        Ok(())
+    }
+}
+
+/**
+ * @notice Deposit totals attributed to a marketing campaign
+ * @dev PDA seeded by the campaign id, shared by every vault
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct CampaignStats {
+    /// Campaign these totals belong to
+    pub campaign_id: u32,
+    /// Total deposited under the campaign in lamports
+    pub total_deposited: u64,
+    /// Number of deposits made under the campaign
+    pub deposit_count: u64,
+    /// Bump seed for the campaign stats PDA
+    pub bump: u8,
+}
+
+impl CampaignStats {
+    /// Seed constant for campaign stats PDA
+    pub const SEED: &'static [u8] = b"campaign";
+
+    /**
+     * @notice Adds a deposit to the campaign's totals, filling in the identity on first use
+     * @param campaign_id Campaign the deposit is attributed to
+     * @param amount Amount deposited in lamports
+     * @param bump Bump seed of the campaign stats PDA
+     * @return Result<&Self> Updated totals or MathOverflow
+     */
+    pub fn record(&mut self, campaign_id: u32, amount: u64, bump: u8) -> Result<&Self> {
+        self.campaign_id = campaign_id;
+        self.bump = bump;
+        self.total_deposited = self.total_deposited.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+        self.deposit_count = self.deposit_count.checked_add(1).ok_or(VaultError::MathOverflow)?;
+
+        Ok(self)
+    }
+}
+
+/**
+ * @notice Protocol-wide totals for dashboards, read off-chain
+ * @dev Singleton PDA created by the first initialize. TVL counts every lamport the program
+ * moves into or out of a vault, including the rent seeded at initialization; lamports sent
+ * to a vault PDA by a plain transfer are not seen until the vault is closed
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct GlobalStats {
+    /// Number of open vaults
+    pub vault_count: u64,
+    /// Lamports held across all vaults
+    pub total_value_locked: u64,
+    /// Bump seed for the global stats PDA
+    pub bump: u8,
+}
+
+impl GlobalStats {
+    /// Seed constant for global stats PDA
+    pub const SEED: &'static [u8] = b"global_stats";
+
+    /**
+     * @notice Counts a newly initialized vault and its seeded balance
+     * @param balance Vault balance after seeding in lamports
+     * @return Result<()> Success or MathOverflow
+     */
+    pub fn open_vault(&mut self, balance: u64) -> Result<()> {
+        self.vault_count = self.vault_count.checked_add(1).ok_or(VaultError::MathOverflow)?;
+        self.record_inflow(balance)
+    }
+
+    /**
+     * @notice Removes a closed vault and everything it held
+     * @dev Saturates so vaults opened before the stats account existed can still close
+     * @param balance Vault balance paid out by the close in lamports
+     */
+    pub fn close_vault(&mut self, balance: u64) {
+        self.vault_count = self.vault_count.saturating_sub(1);
+        self.record_outflow(balance);
+    }
+
+    /**
+     * @notice Adds lamports moved into a vault
+     * @param amount Amount received in lamports
+     * @return Result<()> Success or MathOverflow
+     */
+    pub fn record_inflow(&mut self, amount: u64) -> Result<()> {
+        self.total_value_locked = self.total_value_locked.checked_add(amount).ok_or(VaultError::MathOverflow)?;
+        Ok(())
+    }
+
+    /**
+     * @notice Subtracts lamports moved out of a vault
+     * @dev Saturates so vaults opened before the stats account existed can still be emptied
+     * @param amount Amount paid out in lamports
+     */
+    pub fn record_outflow(&mut self, amount: u64) {
+        self.total_value_locked = self.total_value_locked.saturating_sub(amount);
+    }
+}
+
+/**
+ * @notice Operator-funded reward program paid into vaults by accrue
+ * @dev Singleton PDA; rewards come from a separate system-owned treasury PDA
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct RewardConfig {
+    /// Reward per 10^12 lamports of vault balance per second
+    pub rate: u64,
+    /// Bump seed for the reward config PDA
+    pub bump: u8,
+    /// Bump seed for the reward treasury PDA
+    pub treasury_bump: u8,
+}
+
+impl RewardConfig {
+    /// Seed constant for reward config PDA
+    pub const SEED: &'static [u8] = b"reward_config";
+    /// Seed constant for the reward treasury PDA
+    pub const TREASURY_SEED: &'static [u8] = b"reward_treasury";
+}
+
+/**
+ * @notice Operator-funded deposit match campaign paid into vaults by deposit_matched
+ * @dev Singleton PDA; matches come from a separate system-owned treasury PDA
+ */
+#[account]
+#[derive(InitSpace)]
+pub struct MatchConfig {
+    /// Share of each deposit matched, out of BPS_DENOMINATOR
+    pub ratio_bps: u16,
+    /// Lamports the campaign can still match across all vaults
+    pub budget: u64,
+    /// Maximum lamports matched into a single vault over its lifetime (0 for no cap)
+    pub per_vault_cap: u64,
+    /// Bump seed for the match config PDA
+    pub bump: u8,
+    /// Bump seed for the match treasury PDA
+    pub treasury_bump: u8,
+}
+
+impl MatchConfig {
+    /// Seed constant for match config PDA
+    pub const SEED: &'static [u8] = b"match_config";
+    /// Seed constant for the match treasury PDA
+    pub const TREASURY_SEED: &'static [u8] = b"match_treasury";
+}
+
+// Return data for read-only queries
+
+/**
+ * @notice Addresses and bump seeds of a vault, returned by get_vault_info
+ * @dev Lets clients check their PDA derivation against the program
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VaultInfo {
+    /// Vault state PDA, derived from STATE_SEED, the owner and the index
+    pub vault_state: Pubkey,
+    /// Vault PDA holding the lamports, derived from VAULT_SEED, the owner and the index
+    pub vault: Pubkey,
+    /// Index seed of the vault among its owner's vaults
+    pub index: u64,
+    /// Bump seed of the vault state PDA
+    pub state_bump: u8,
+    /// Bump seed of the vault PDA
+    pub vault_bump: u8,
+    /// Current vault balance in lamports, including the rent-exempt minimum
+    pub balance: u64,
+}
+
+/**
+ * @notice Final settlement of a closed vault, returned by close, confirm_close, close_to and close_expired
+ * @dev Lets programs closing vaults through CPI read the result without parsing logs
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CloseSettlement {
+    /// Owner of the closed vault
+    pub owner: Pubkey,
+    /// Vault balance paid out on close in lamports, including the rent-exempt minimum
+    pub final_balance: u64,
+}
+
+/**
+ * @notice Summary of the limits enforced on a vault, returned by get_constraints
+ * @dev Fields are only ever appended and layout_version is bumped when they are,
+ * so clients decoding an older prefix keep working
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VaultConstraints {
+    /// Layout version of this struct
+    pub layout_version: u8,
+    /// Minimum amount accepted by a single deposit
+    pub min_deposit: u64,
+    /// Maximum amount allowed by a single withdrawal
+    pub max_withdrawal: u64,
+    /// Minimum amount for a single withdrawal (0 when disabled)
+    pub min_withdrawal: u64,
+    /// Balance the vault must always retain (rent-exempt minimum)
+    pub min_balance: u64,
+    /// Withdrawals above this amount require approvals
+    pub approval_threshold: u64,
+    /// Distinct approver signatures required above the threshold (0 when disabled)
+    pub required_approvals: u8,
+    /// Bitmask of paused instructions, see the VaultState::PAUSE_* bits
+    pub paused_instructions: u8,
+    /// Unix timestamp before which withdrawals are locked (0 when unlocked)
+    pub unlock_time: i64,
+    /// Maximum withdrawn per UTC day (0 when unlimited)
+    pub daily_limit: u64,
+    /// Maximum balance above the rent-exempt minimum (0 when uncapped)
+    pub max_balance: u64,
+    /// Current floor withdrawals keep above rent and committed funds (0 when disabled)
+    pub balance_floor: u64,
+    /// Maximum withdrawals per hour (0 when unlimited)
+    pub max_withdrawals_per_window: u32,
+    /// Maximum lifetime total withdrawn (0 when unlimited)
+    pub lifetime_withdraw_cap: u64,
+}
+
+impl VaultConstraints {
+    /// Current layout version of the constraints summary
+    pub const LAYOUT_VERSION: u8 = 9;
+}
+
+/**
+ * @notice Outcome of each withdrawal limit for a prospective amount, returned by check_rate_limits
+ * @dev allowed ignores approvals, which depend on signatures only known at submission time
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// Whether the withdrawal would pass every limit (approvals aside)
+    pub allowed: bool,
+    /// Whether the amount is non-zero
+    pub valid_amount: bool,
+    /// Whether the amount is within the per-transaction maximum
+    pub within_per_tx_limit: bool,
+    /// Per-transaction maximum in lamports
+    pub per_tx_limit: u64,
+    /// Whether the amount meets the vault's minimum withdrawal
+    pub meets_minimum: bool,
+    /// Minimum withdrawal in lamports (0 when disabled)
+    pub min_withdrawal: u64,
+    /// Whether the amount fits in the balance above rent, committed funds, the balance floor and locked savings
+    pub within_available: bool,
+    /// Amount currently available for withdrawal in lamports
+    pub available: u64,
+    /// Whether the withdrawal needs approver signatures
+    pub requires_approvals: bool,
+    /// Whether withdrawals are paused on the vault
+    pub paused: bool,
+    /// Whether the deposit recency policy is satisfied
+    pub recent_deposit: bool,
+    /// Whether the time lock still blocks withdrawals
+    pub locked: bool,
+    /// Whether the amount fits in what is left of today's limit
+    pub within_daily_limit: bool,
+    /// Lamports still withdrawable today (u64::MAX when unlimited)
+    pub daily_remaining: u64,
+    /// Whether another withdrawal fits in the current withdrawal window
+    pub within_withdrawal_window: bool,
+    /// Whether the owner has permanently renounced withdrawals
+    pub renounced: bool,
+}
+
+/**
+ * @notice A page of access log entries, returned by get_log_page
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LogPage {
+    /// Number of entries ever recorded, including overwritten ones
+    pub total_entries: u64,
+    /// Number of entries currently stored in the log
+    pub stored: u8,
+    /// Requested entries, oldest first
+    pub entries: Vec<AccessEntry>,
+}
+
+/**
+ * @notice A slice of the serialized vault state, returned by read_state
+ */
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StateChunk {
+    /// Length of the full serialization, discriminator included
+    pub total_len: u32,
+    /// Byte offset of the first byte in data
+    pub offset: u32,
+    /// Serialized bytes starting at offset
+    pub data: Vec<u8>,
+}
+
+// Events for program activity tracking
+
+/**
+ * @notice Event emitted when a vault is initialized
+ */
+#[event]
+pub struct VaultInitialized {
+    pub event_version: u8,
+    pub category: u8,
+    pub user: Pubkey,
+    pub payer: Pubkey,
+    pub vault: Pubkey,
+    pub vault_state: Pubkey,
+    pub index: u64,
+    pub external_ref: u64,
+    pub label: [u8; LABEL_LEN],
+    pub created_slot: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+impl VaultInitialized {
+    /// Category tag shared by every emission of this event
+    pub const CATEGORY: u8 = 0;
+}
+
+/**
+ * @notice Event emitted when funds are deposited
+ */
+#[event]
+pub struct FundsDeposited {
+    pub event_version: u8,
+    pub category: u8,
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub points_earned: u64,
+    pub total_deposited: u64,
+    pub source_count: u8,
+    pub memo: [u8; MEMO_LEN],
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+impl FundsDeposited {
+    /// Category tag shared by every emission of this event
+    pub const CATEGORY: u8 = 1;
+}
+
+/**
+ * @notice Event emitted when funds are withdrawn
+ */
+#[event]
+pub struct FundsWithdrawn {
+    pub event_version: u8,
+    pub category: u8,
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub total_withdrawn: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+    pub initiated_by_admin: bool,
+}
+
+impl FundsWithdrawn {
+    /// Category tag shared by every emission of this event
+    pub const CATEGORY: u8 = 2;
+}
+
+/**
+ * @notice Event emitted instead of FundsDeposited and FundsWithdrawn when compact events are enabled
+ * @dev The vault is identified by the transaction's accounts, so only the kind and amount are carried
+ */
+#[event]
+pub struct CompactEvent {
+    pub kind: u8,
+    pub value: u64,
+}
+
+impl CompactEvent {
+    /// Kind tag for a deposit
+    pub const KIND_DEPOSIT: u8 = 0;
+    /// Kind tag for a withdrawal
+    pub const KIND_WITHDRAW: u8 = 1;
+}
+
+/**
+ * @notice Event emitted on demand with every tracked field and the live balance
+ * @dev Embeds the full VaultState so new fields are covered automatically
+ */
+#[event]
+pub struct FullStateEvent {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub vault_state: Pubkey,
+    pub balance: u64,
+    pub rent_exempt: u64,
+    pub state: VaultState,
+}
+
+/**
+ * @notice Event emitted when a mirror vault is synced to its mirrored vault
+ */
+#[event]
+pub struct MirrorSynced {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub mirrored_vault: Pubkey,
+    pub previous_balance: u64,
+    pub new_balance: u64,
+    pub fee: u64,
+}
+
+/**
+ * @notice Event emitted when a stream is created
+ */
+#[event]
+pub struct StreamCreated {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub recipient: Pubkey,
+    pub total: u64,
+    pub rate_per_sec: u64,
+    pub start: i64,
+}
+
+/**
+ * @notice Event emitted when a recipient claims vested stream funds
+ */
+#[event]
+pub struct StreamClaimed {
+    pub vault: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub claimed_total: u64,
+}
+
+/**
+ * @notice Event emitted when a stream is cancelled by its creator
+ */
+#[event]
+pub struct StreamCancelled {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub recipient: Pubkey,
+    pub vested: u64,
+    pub returned: u64,
+}
+
+/**
+ * @notice Event emitted when a contributor funds a campaign
+ */
+#[event]
+pub struct ContributionReceived {
+    pub contributor: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub total_raised: u64,
+}
+
+/**
+ * @notice Event emitted when a contribution is refunded after a missed goal
+ */
+#[event]
+pub struct ContributionRefunded {
+    pub contributor: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+}
+
+/**
+ * @notice Event emitted when an access attempt is logged
+ */
+#[event]
+pub struct AccessLogged {
+    pub vault_state: Pubkey,
+    pub accessor: Pubkey,
+    pub timestamp: i64,
+    pub purpose: String,
+}
+
+/**
+ * @notice Event emitted when a vault's funds are migrated to a successor program
+ */
+#[event]
+pub struct VaultMigrated {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub target_program: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+/**
+ * @notice Event emitted when an owner grants spending authority
+ */
+#[event]
+pub struct SpendingGranted {
+    pub user: Pubkey,
+    pub vault_state: Pubkey,
+    pub grantee: Pubkey,
+    pub ceiling: u64,
+    pub expires_at: i64,
+}
+
+/**
+ * @notice Event emitted when a grantee spends against a grant
+ */
+#[event]
+pub struct GrantSpent {
+    pub grantee: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub remaining: u64,
+}
+
+/**
+ * @notice Event emitted when a withdrawal trips a suspicious activity heuristic
+ * @dev Off-chain watchers keyed to contact can alert the vault owner
+ */
+#[event]
+pub struct SuspiciousActivity {
+    pub vault: Pubkey,
+    pub contact: Pubkey,
+    pub reason_code: u8,
+}
+
+impl SuspiciousActivity {
+    /// Reason code for a withdrawal above the large withdrawal share of the balance
+    pub const REASON_LARGE_WITHDRAWAL: u8 = 0;
+    /// Reason code for too many withdrawals within the rapid window
+    pub const REASON_RAPID_WITHDRAWALS: u8 = 1;
+}
+
+/**
+ * @notice Event emitted once when deposits first push the lifetime total over the reporting threshold
+ */
+#[event]
+pub struct ReportingThresholdCrossed {
+    pub user: Pubkey,
+    pub total: u64,
+}
+
+/**
+ * @notice Event logged by a withdrawal rejected for breaking rent exemption
+ * @dev Only visible in the logs of the failed transaction, e.g. from a simulation
+ */
+#[event]
+pub struct WithdrawRejected {
+    pub vault: Pubkey,
+    pub requested: u64,
+    pub max_allowed: u64,
+}
+
+/**
+ * @notice Event emitted when a recipient is added to or removed from an owner's whitelist
+ */
+#[event]
+pub struct WhitelistUpdated {
+    pub owner: Pubkey,
+    pub recipient: Pubkey,
+    pub added: bool,
+}
+
+/**
+ * @notice Event emitted when the owner requests a delayed close
  */
 #[event]
-pub struct VaultInitialized {
+pub struct CloseRequested {
     pub user: Pubkey,
-    pub vault: Pubkey,
     pub vault_state: Pubkey,
+    pub close_after: i64,
 }
 
 /**
- * @notice Event emitted when funds are deposited
+ * @notice Event emitted when the owner permanently renounces withdrawals
  */
 #[event]
-pub struct FundsDeposited {
+pub struct WithdrawalsRenounced {
+    pub user: Pubkey,
+    pub vault_state: Pubkey,
+}
+
+/**
+ * @notice Event emitted when the owner sets or extends the vault's time lock
+ */
+#[event]
+pub struct VaultLocked {
+    pub user: Pubkey,
+    pub vault_state: Pubkey,
+    pub unlock_time: i64,
+}
+
+/**
+ * @notice Event emitted the first time a deposit brings the vault to its savings goal
+ */
+#[event]
+pub struct GoalReached {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub goal: u64,
+    pub balance: u64,
+}
+
+/**
+ * @notice Event emitted when rewards are accrued into a vault
+ */
+#[event]
+pub struct RewardAccrued {
     pub user: Pubkey,
     pub vault: Pubkey,
     pub amount: u64,
+    pub elapsed: i64,
 }
 
 /**
- * @notice Event emitted when funds are withdrawn
+ * @notice Event emitted when a matched deposit completes, with a zero match once the campaign runs out
  */
 #[event]
-pub struct FundsWithdrawn {
+pub struct DepositMatched {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub user_amount: u64,
+    pub matched_amount: u64,
+}
+
+/**
+ * @notice Event emitted when SPL tokens are deposited
+ */
+#[event]
+pub struct TokenDeposited {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/**
+ * @notice Event emitted when SPL tokens are withdrawn
+ */
+#[event]
+pub struct TokenWithdrawn {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/**
+ * @notice Event emitted when a beneficiary is paid their share of a closed vault
+ */
+#[event]
+pub struct BeneficiaryPaid {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+}
+
+/**
+ * @notice Event emitted when a guardian starts recovering a vault
+ */
+#[event]
+pub struct RecoveryInitiated {
+    pub owner: Pubkey,
+    pub guardian: Pubkey,
+    pub new_authority: Pubkey,
+    pub finalize_after: i64,
+}
+
+/**
+ * @notice Event emitted when an owner proposes a new authority for their vault
+ */
+#[event]
+pub struct OwnershipTransferInitiated {
+    pub owner: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+/**
+ * @notice Event emitted when a vault is handed to a new authority
+ */
+#[event]
+pub struct OwnershipTransferred {
+    pub old: Pubkey,
+    pub new: Pubkey,
+}
+
+/**
+ * @notice Event emitted when the program-wide pause is toggled
+ */
+#[event]
+pub struct ProgramPauseSet {
+    pub admin: Pubkey,
+    pub paused: bool,
+}
+
+/**
+ * @notice Event emitted when a vault is drained to its safe address
+ */
+#[event]
+pub struct PanicDrained {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub safe_address: Pubkey,
+    pub amount: u64,
+}
+
+/**
+ * @notice Event emitted when a deposit is attributed to a campaign
+ */
+#[event]
+pub struct CampaignDeposit {
+    pub campaign_id: u32,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub total_deposited: u64,
+    pub deposit_count: u64,
+}
+
+/**
+ * @notice Event emitted when withdraw_with_save diverts part of a withdrawal into locked savings
+ */
+#[event]
+pub struct SavingsLocked {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub tranche: u64,
+    pub unlock_time: i64,
+}
+
+/**
+ * @notice Event emitted when a delegate withdraws to the owner
+ */
+#[event]
+pub struct DelegateWithdrawn {
+    pub delegate: Pubkey,
+    pub owner: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+/**
+ * @notice Event emitted when the owner schedules a withdrawal
+ */
+#[event]
+pub struct WithdrawalScheduled {
+    pub user: Pubkey,
+    pub vault_state: Pubkey,
+    pub amount: u64,
+    pub recipient: Pubkey,
+    pub execute_after: i64,
+}
+
+/**
+ * @notice Event emitted when the owner cancels a scheduled withdrawal
+ */
+#[event]
+pub struct ScheduledWithdrawalCancelled {
+    pub user: Pubkey,
+    pub vault_state: Pubkey,
+    pub amount: u64,
+}
+
+/**
+ * @notice Event emitted when the owner adds to the vault's rent buffer
+ */
+#[event]
+pub struct RentBufferFunded {
+    pub user: Pubkey,
+    pub vault: Pubkey,
+    pub extra: u64,
+    pub rent_buffer: u64,
+}
+
+/**
+ * @notice Event emitted when a vault is emptied down to its rent-exempt minimum without closing
+ */
+#[event]
+pub struct VaultDrained {
     pub user: Pubkey,
     pub vault: Pubkey,
     pub amount: u64,
@@ -358,9 +8842,19 @@ pub struct FundsWithdrawn {
  */
 #[event]
 pub struct VaultClosed {
+    pub event_version: u8,
+    pub category: u8,
     pub user: Pubkey,
     pub vault: Pubkey,
+    pub destination: Pubkey,
     pub final_balance: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+impl VaultClosed {
+    /// Category tag shared by every emission of this event
+    pub const CATEGORY: u8 = 3;
 }
 
 // Custom error definitions
@@ -370,7 +8864,7 @@ pub struct VaultClosed {
  */
 #[error_code]
 pub enum VaultError {
-    #[msg("Deposit amount must be at least 1000 lamports")]
+    #[msg("Deposit amount is below the vault's minimum deposit")]
     InsufficientDepositAmount,
     
     #[msg("Withdrawal amount must be greater than 0")]
@@ -381,4 +8875,361 @@ pub enum VaultError {
     
     #[msg("Insufficient funds in vault after withdrawal to maintain rent exemption")]
     InsufficientFundsAfterWithdrawal,
+
+    #[msg("Withdrawal amount is below the vault's minimum withdrawal")]
+    WithdrawalTooSmall,
+
+    #[msg("Vault is not configured as a mirror")]
+    MirrorNotSet,
+
+    #[msg("A vault cannot mirror itself")]
+    InvalidMirrorTarget,
+
+    #[msg("Too many approvers configured")]
+    TooManyApprovers,
+
+    #[msg("Invalid approval configuration")]
+    InvalidApprovalConfig,
+
+    #[msg("Withdrawal requires more approver signatures")]
+    InsufficientApprovals,
+
+    #[msg("Withdrawal would use funds committed to a stream")]
+    FundsCommitted,
+
+    #[msg("Invalid stream parameters")]
+    InvalidStreamParams,
+
+    #[msg("Vault already has an active stream")]
+    StreamAlreadyActive,
+
+    #[msg("Signer is not the stream recipient")]
+    NotStreamRecipient,
+
+    #[msg("No vested stream funds to claim")]
+    NothingToClaim,
+
+    #[msg("Vault has no active stream")]
+    NoActiveStream,
+
+    #[msg("Invalid funding goal or deadline")]
+    InvalidFundingParams,
+
+    #[msg("A funding campaign is still active")]
+    FundingCampaignActive,
+
+    #[msg("Vault has no funding campaign")]
+    NoFundingCampaign,
+
+    #[msg("Funding campaign deadline has passed")]
+    FundingClosed,
+
+    #[msg("Funding goal was met, contributions are not refundable")]
+    GoalMetNoRefund,
+
+    #[msg("Funding deadline has not passed yet")]
+    DeadlineNotPassed,
+
+    #[msg("Access purpose exceeds the maximum length")]
+    PurposeTooLong,
+
+    #[msg("Vault only holds its rent-exempt minimum, which is reclaimable on close")]
+    OnlyRentRemains,
+
+    #[msg("Vault has not been prepared for migration")]
+    MigrationNotPrepared,
+
+    #[msg("Migration target must be an executable program other than this one")]
+    InvalidMigrationTarget,
+
+    #[msg("Vault has active streams or refundable contributions")]
+    ActiveCommitmentsExist,
+
+    #[msg("Stream recipient account is required to settle the active stream")]
+    StreamRecipientRequired,
+
+    #[msg("Rounding unit must be greater than zero")]
+    InvalidRoundingUnit,
+
+    #[msg("This instruction is paused for the vault")]
+    InstructionPaused,
+
+    #[msg("Clock timestamp is outside the vault's plausible range")]
+    ImplausibleTimestamp,
+
+    #[msg("Maximum timestamp must be after the vault's creation time")]
+    InvalidClockBound,
+
+    #[msg("Spending grant needs a non-zero ceiling and a future expiry")]
+    InvalidGrantParams,
+
+    #[msg("Spending grant has expired")]
+    GrantExpired,
+
+    #[msg("Amount exceeds the remaining spending grant ceiling")]
+    GrantCeilingExceeded,
+
+    #[msg("Deposit exceeds the confirmation threshold and must be proposed first")]
+    DepositRequiresConfirmation,
+
+    #[msg("Confirmation window must be positive when a threshold is set")]
+    InvalidConfirmationWindow,
+
+    #[msg("No deposit has been proposed")]
+    NoProposedDeposit,
+
+    #[msg("Deposit proposal confirmation window has passed")]
+    ConfirmationExpired,
+
+    #[msg("Log page is out of bounds")]
+    InvalidLogPage,
+
+    #[msg("Invalid suspicious activity monitoring configuration")]
+    InvalidMonitorConfig,
+
+    #[msg("Converted amount does not fit in a u64")]
+    ConversionOverflow,
+
+    #[msg("Unknown authorization action")]
+    InvalidAction,
+
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+
+    #[msg("Deposit recency must not be negative")]
+    InvalidDepositRecency,
+
+    #[msg("No deposit was made recently enough to allow a withdrawal")]
+    NoRecentDeposit,
+
+    #[msg("Vault is time-locked")]
+    VaultLocked,
+
+    #[msg("Tag must be 1 to 8 bytes without null bytes")]
+    InvalidTag,
+
+    #[msg("Vault already has this tag")]
+    DuplicateTag,
+
+    #[msg("Vault already has the maximum number of tags")]
+    TooManyTags,
+
+    #[msg("Vault does not have this tag")]
+    TagNotFound,
+
+    #[msg("Vault is already tied to a mint")]
+    TokenVaultAlreadyInitialized,
+
+    #[msg("Vault is not tied to a mint")]
+    NotTokenVault,
+
+    #[msg("Token account mint does not match the vault's mint")]
+    MintMismatch,
+
+    #[msg("Withdrawal would exceed the vault's daily limit")]
+    DailyLimitExceeded,
+
+    #[msg("Too many beneficiaries configured")]
+    TooManyBeneficiaries,
+
+    #[msg("Beneficiary shares must be distinct, non-zero and sum to 10000 basis points")]
+    InvalidBeneficiaryShares,
+
+    #[msg("Beneficiary accounts do not match the configured beneficiaries")]
+    BeneficiaryMismatch,
+
+    #[msg("Signer is not the vault's authority")]
+    NotVaultAuthority,
+
+    #[msg("New authority must differ from the current authority")]
+    InvalidNewAuthority,
+
+    #[msg("Vaults tied to a mint cannot be transferred")]
+    TokenVaultNotTransferable,
+
+    #[msg("Program is paused by the admin")]
+    ProgramPaused,
+
+    #[msg("Signer is not the program's upgrade authority")]
+    NotUpgradeAuthority,
+
+    #[msg("Signer is not the config admin")]
+    NotConfigAdmin,
+
+    #[msg("Vault has no safe address configured")]
+    SafeAddressNotSet,
+
+    #[msg("Safe address was set too recently to be trusted")]
+    SafeAddressNotTrusted,
+
+    #[msg("Safe address delay cannot be negative")]
+    InvalidSafeAddressDelay,
+
+    #[msg("Deposit would exceed the vault's maximum balance")]
+    ExceedsMaxBalance,
+
+    #[msg("Percentage must be at most 10000 basis points")]
+    InvalidPercent,
+
+    #[msg("Vault is already initialized")]
+    AlreadyInitialized,
+
+    #[msg("Withdrawal would dip below the vault's balance floor")]
+    BelowBalanceFloor,
+
+    #[msg("Payment needs a non-zero amount and a recipient other than the user and the vault")]
+    InvalidPayment,
+
+    #[msg("Signer is not the vault's delegate")]
+    NotVaultDelegate,
+
+    #[msg("The vault owner cannot be its own delegate")]
+    InvalidDelegate,
+
+    #[msg("Withdrawal would use the locked savings tranche")]
+    SavingsLocked,
+
+    #[msg("Savings lock period must not be negative")]
+    InvalidSavingsLock,
+
+    #[msg("Vault has no savings lock period configured")]
+    SavingsLockNotSet,
+
+    #[msg("Minimum deposit must be greater than zero")]
+    InvalidMinDeposit,
+
+    #[msg("Batch deposit has more than 10 funding sources")]
+    TooManySources,
+
+    #[msg("Each amount needs exactly one signing, writable funding source")]
+    InvalidFundingSources,
+
+    #[msg("Withdrawal fee must be at most 1000 basis points")]
+    InvalidWithdrawFee,
+
+    #[msg("Treasury account does not match the program config")]
+    InvalidTreasury,
+
+    #[msg("Signer is not the vault's guardian")]
+    NotVaultGuardian,
+
+    #[msg("The vault owner cannot be its own guardian")]
+    InvalidGuardian,
+
+    #[msg("Vault has no pending recovery")]
+    NoRecoveryPending,
+
+    #[msg("Recovery cooldown has not passed yet")]
+    RecoveryCooldownActive,
+
+    #[msg("Vault balance has not reached its savings goal")]
+    GoalNotReached,
+
+    #[msg("Withdrawal exceeds the vested amount not yet withdrawn")]
+    ExceedsVested,
+
+    #[msg("Vesting duration must be greater than zero")]
+    InvalidVestingDuration,
+
+    #[msg("A vesting window is already running")]
+    VestingActive,
+
+    #[msg("Maximum withdrawal must be greater than zero")]
+    InvalidMaxWithdrawal,
+
+    #[msg("Close delay has not passed yet")]
+    CloseDelayActive,
+
+    #[msg("Vault has no pending close request")]
+    NoCloseRequested,
+
+    #[msg("Close delay must not be negative")]
+    InvalidCloseDelay,
+
+    #[msg("Recipient is not on the owner's withdrawal whitelist")]
+    RecipientNotWhitelisted,
+
+    #[msg("Recipient is already whitelisted")]
+    DuplicateRecipient,
+
+    #[msg("Withdrawal whitelist holds at most 10 recipients")]
+    WhitelistFull,
+
+    #[msg("Recipient must be a writable system account other than the default address")]
+    InvalidRecipient,
+
+    #[msg("Too many withdrawals in the current window")]
+    TooManyWithdrawals,
+
+    #[msg("Withdrawals have been permanently renounced for this vault")]
+    WithdrawalsRenounced,
+
+    #[msg("Split withdrawals pay at most 10 recipients")]
+    TooManyRecipients,
+
+    #[msg("Each split amount needs a recipient account")]
+    RecipientCountMismatch,
+
+    #[msg("Withdrawal would dip into the vault's reserve")]
+    ReserveViolation,
+
+    #[msg("Withdrawals cannot be invoked through CPI while the CPI guard is on")]
+    CpiNotAllowed,
+
+    #[msg("Withdrawal would use the partially locked amount")]
+    AmountLocked,
+
+    #[msg("Scheduled withdrawal needs a non-zero amount, a recipient and a future execution time")]
+    InvalidScheduledWithdrawal,
+
+    #[msg("Scheduled withdrawal cannot be executed yet")]
+    NotYetExecutable,
+
+    #[msg("Vault has no scheduled withdrawal")]
+    NoScheduledWithdrawal,
+
+    #[msg("Expiry must be in the future, or zero to remove it")]
+    InvalidExpiry,
+
+    #[msg("Vault has not expired")]
+    NotExpired,
+
+    #[msg("Vault still holds lamports after being drained for close")]
+    CloseIncomplete,
+
+    #[msg("Token account does not hold wrapped SOL")]
+    NotNativeMint,
+
+    #[msg("Signer is not the vault's pending authority")]
+    NotPendingAuthority,
+
+    #[msg("Vault has no pending ownership transfer")]
+    NoPendingTransfer,
+
+    #[msg("Unlock time is in the past or closer than the minimum lock duration")]
+    LockTooShort,
+
+    #[msg("Minimum lock duration cannot be negative")]
+    InvalidLockDuration,
+
+    #[msg("Vault holds lamports, close it instead")]
+    VaultFunded,
+
+    #[msg("Withdrawal would exceed the vault's lifetime withdrawal cap")]
+    LifetimeCapExceeded,
+
+    #[msg("State offset is past the end of the serialized vault state")]
+    InvalidStateOffset,
+
+    #[msg("Vault state is inconsistent")]
+    InvariantViolation,
+
+    #[msg("Vault has not opted into admin withdrawals")]
+    NotAdminManaged,
+
+    #[msg("Lifetime withdrawal cap can only be lowered once set")]
+    LifetimeCapLocked,
+
+    #[msg("Token vault still holds tokens, withdraw them before closing")]
+    TokenBalanceRemaining,
 }